@@ -1,15 +1,246 @@
 #![no_std]
-use soroban_sdk::{contract, contractimpl, contracttype, Env, Address, String};
+use soroban_sdk::auth::{ContractContext, InvokerContractAuthEntry, SubContractInvocation};
+use soroban_sdk::xdr::ToXdr;
+use soroban_sdk::{contract, contractclient, contractevent, contractimpl, contracttype, token, vec, Env, Address, Bytes, BytesN, IntoVal, Map, String, Symbol, TryFromVal, Val, Vec};
 
 // Data structures for the Emergency Fund Release DAO
 
+/// Minimum number of votes before a decision can be made
+const MIN_VOTES: u32 = 3;
+
+/// Default time payees have to claim their balance (90 days)
+const DEFAULT_CLAIM_PERIOD: u64 = 90 * 24 * 60 * 60;
+
+/// Default time patient data is kept after a proposal closes (1 year)
+const DEFAULT_RETENTION_PERIOD: u64 = 365 * 24 * 60 * 60;
+
+/// Default time a hospital has to appeal a rejection (30 days)
+const DEFAULT_APPEAL_WINDOW: u64 = 30 * 24 * 60 * 60;
+
+/// Default approval percentage appeals need
+const DEFAULT_APPEAL_THRESHOLD: u32 = 75;
+
+/// Ledgers donation records are kept alive for (~30 days at 5s per ledger)
+const RECORD_TTL: u32 = 30 * 17280;
+
+/// Remaining ledgers below which a donation record's TTL is extended
+const RECORD_TTL_THRESHOLD: u32 = RECORD_TTL - 17280;
+
 #[derive(Clone)]
 #[contracttype]
 pub enum ProposalStatus {
     Active,
     Approved,
     Rejected,
+    PartiallyExecuted,
+    Escrowed,        // Funded and held by the DAO, released by milestone or stream
     Executed,
+    Cancelled,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum DissolutionStatus {
+    Voting,
+    Approved,        // Winding down; refunds open once the grace period ends
+    Rejected,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum PayeeRole {
+    Hospital,
+    Pharmacy,
+    Ambulance,
+    Family,
+    Other,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum AttachmentKind {
+    Diagnosis,
+    CostEstimate,
+    Invoice,
+    DischargeSummary,
+    AppealEvidence,  // New evidence filed with an appeal
+    Other,
+}
+
+/// Asset identifier used by SEP-40 price oracles
+#[derive(Clone)]
+#[contracttype]
+pub enum Asset {
+    Stellar(Address),
+    Other(Symbol),
+}
+
+/// Price record returned by SEP-40 price oracles
+#[derive(Clone)]
+#[contracttype]
+pub struct PriceData {
+    pub price: i128,
+    pub timestamp: u64,
+}
+
+/// The part of the SEP-40 oracle interface the DAO relies on
+#[contractclient(name = "PriceOracleClient")]
+pub trait PriceOracle {
+    fn lastprice(env: Env, asset: Asset) -> Option<PriceData>;
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct OracleConfig {
+    pub oracle: Address,
+    pub max_price_age: u64,     // Seconds before a price is considered stale
+    pub max_slippage_bps: u32,  // Allowed increase over the quote taken at approval
+}
+
+/// Generic yield vault interface holding a single token
+/// `deposit` pulls `amount` from `from`; `balance` includes accrued yield.
+#[contractclient(name = "YieldVaultClient")]
+pub trait YieldVault {
+    fn deposit(env: Env, from: Address, amount: i128);
+    fn withdraw(env: Env, to: Address, amount: i128);
+    fn balance(env: Env, owner: Address) -> i128;
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct VaultConfig {
+    pub vault: Address,
+    pub liquidity_floor: i128,  // General funds that must stay liquid in the DAO
+    pub principal: i128,        // General funds currently deposited in the vault
+}
+
+/// A single payee of a proposal and the amount requested for them
+#[derive(Clone)]
+#[contracttype]
+pub struct LineItem {
+    pub payee: Address,
+    pub amount: i128,
+    pub role: PayeeRole,
+}
+
+/// What a proposal's amounts are counted in: its token, or its fiat currency
+#[derive(Clone, PartialEq)]
+#[contracttype]
+pub enum Denomination {
+    Token(Address),
+    Fiat(Symbol),
+}
+
+/// Limits on what a single patient can receive across proposals in one
+/// denomination. A cap of 0 means no limit.
+#[derive(Clone)]
+#[contracttype]
+pub struct PatientCaps {
+    pub lifetime_cap: i128,
+    pub period_cap: i128,
+    pub period: u64,         // Seconds the period cap looks back over
+    pub reject: bool,        // Reject proposals over a cap instead of flagging them
+}
+
+/// Off-chain case file of a proposal. Patient identity and medical details
+/// never go on-chain; only commitments to them are stored.
+#[derive(Clone)]
+#[contracttype]
+pub struct CaseFile {
+    pub patient_commitment: BytesN<32>,  // sha256(salt || patient name)
+    pub patient_id: BytesN<32>,          // sha256(DAO pepper || national id), the same in every proposal
+    pub content_hash: BytesN<32>,        // sha256 of the encrypted case file
+    pub uri: String,                     // Where the encrypted case file is stored
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum ReviewStage {
+    Triage,          // Completeness check by a coordinator
+    MedicalReview,   // Designated reviewers approve or request changes
+    Voting,          // Open for member votes
+    Returned,        // Sent back to the hospital for changes
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum ReviewRole {
+    Coordinator,
+    Reviewer,
+}
+
+/// Deadlines and quorum of the review pipeline
+#[derive(Clone)]
+#[contracttype]
+pub struct ReviewPipeline {
+    pub triage_period: u64,      // Seconds each stage has before its deadline
+    pub review_period: u64,
+    pub voting_period: u64,
+    pub reviews_required: u32,   // Reviewer approvals needed to open voting
+}
+
+/// Random per-proposal voting committees
+#[derive(Clone)]
+#[contracttype]
+pub struct CommitteeMode {
+    pub size: u32,
+    pub vote_period: u64,        // Seconds committee members have to vote before being replaced
+}
+
+/// Where a proposal is in the review pipeline
+#[derive(Clone)]
+#[contracttype]
+pub struct Review {
+    pub stage: ReviewStage,
+    pub round: u32,              // Incremented every time the hospital revises the proposal
+    pub deadline: u64,           // End of the current stage
+    pub approved_by: Vec<Address>,  // Reviewers who approved in this round
+}
+
+/// A licensed doctor and the hospital they practise at
+#[derive(Clone)]
+#[contracttype]
+pub struct Doctor {
+    pub hospital: Address,
+    pub license_hash: BytesN<32>,  // sha256 of the medical licence number
+}
+
+/// A doctor's statement that a proposal's treatment is medically necessary
+#[derive(Clone)]
+#[contracttype]
+pub struct Attestation {
+    pub doctor: Address,
+    pub statement_hash: BytesN<32>,
+    pub independent: bool,       // Doctor practises at a different hospital than the proposal's
+    pub attested_at: u64,
+}
+
+/// Attestations a proposal needs before members can vote on it
+#[derive(Clone)]
+#[contracttype]
+pub struct AttestationRule {
+    pub required: u32,
+    pub independent_only: bool,  // Only count doctors from a different hospital
+}
+
+/// How rejected proposals can be appealed
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct AppealRule {
+    pub window: u64,     // Time after the rejection to file an appeal
+    pub threshold: u32,  // Approval percentage an appeal needs
+}
+
+/// Supporting document anchored to a proposal. Attachments are append-only;
+/// version n of a proposal's attachments is the first n of them.
+#[derive(Clone)]
+#[contracttype]
+pub struct Attachment {
+    pub version: u32,
+    pub kind: AttachmentKind,
+    pub hash: BytesN<32>,        // sha256 of the document
+    pub uri: Option<String>,
+    pub added_at: u64,
 }
 
 #[derive(Clone)]
@@ -17,13 +248,193 @@ pub enum ProposalStatus {
 pub struct Proposal {
     pub id: u64,
     pub hospital: Address,
-    pub patient_name: String,
-    pub patient_details: String,
-    pub amount_requested: i128,
+    pub case_file: CaseFile,
+    pub category: Symbol,        // Care category, e.g. trauma, cardiac, pediatric
+    pub token: Address,          // Treasury asset the proposal is paid in
+    pub fiat_currency: Option<Symbol>,  // Set when amounts are in a fiat currency
+    pub fiat_quote: i128,        // Token cost of the approved fiat amount, quoted on finalize
+    pub line_items: Vec<LineItem>,
+    pub amount_requested: i128,  // Sum of the line items
+    pub amount_approved: i128,   // Median of approving voters' amounts, set on finalize
+    pub amount_disbursed: i128,  // Total paid out so far (may be partial)
     pub votes_for: u32,
     pub votes_against: u32,
     pub status: ProposalStatus,
     pub created_at: u64,
+    pub closed_at: u64,          // When the proposal was executed, rejected or cancelled
+    pub tombstone: Option<BytesN<32>>,  // Hash of the case file, set once it is erased
+    pub over_patient_cap: bool,  // Flagged when the patient's funding would exceed a cap
+    pub attestations: u32,       // Medical-necessity attestations received
+    pub committee: Vec<Address>, // Members who alone vote on the proposal (empty = all members)
+    pub committee_deadline: u64, // When absent committee members can be replaced (0 until voting opens)
+    pub appeal_of: Option<u64>,  // Rejected proposal this one appeals
+    pub appealed_by: Option<u64>,  // Appeal filed against this proposal's rejection
+}
+
+/// A milestone as requested by the hospital
+#[derive(Clone)]
+#[contracttype]
+pub struct MilestoneRequest {
+    pub amount: i128,
+    pub description_hash: BytesN<32>,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct Milestone {
+    pub amount: i128,
+    pub description_hash: BytesN<32>,
+    pub evidence_hash: Option<BytesN<32>>,  // Set by the hospital once the stage is done
+    pub confirmations: u32,
+    pub released: bool,
+}
+
+/// Linear vesting of a proposal's approved amount to its hospital
+#[derive(Clone)]
+#[contracttype]
+pub struct Stream {
+    pub amount: i128,       // Total that vests; reduced to the vested part if halted
+    pub claimed: i128,
+    pub start: u64,
+    pub duration: u64,
+    pub halted: bool,
+    pub halt_votes: u32,
+}
+
+/// What a donor restricted their funds to
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum Restriction {
+    Category(Symbol),   // Proposals in a care category, e.g. pediatric
+    Hospital(Address),  // Proposals submitted by a specific hospital
+    Proposal(u64),      // A single proposal
+}
+
+/// Public contributions escrowed against a single proposal
+#[derive(Clone)]
+#[contracttype]
+pub struct Crowdfund {
+    pub contributed: i128,  // Total ever contributed
+    pub balance: i128,      // Still held in escrow
+    pub refund_pool: i128,  // Escrow left when the proposal closed, shared pro rata
+    pub refunded: i128,     // Contributions already refunded from the pool
+    pub closed: bool,
+}
+
+/// Published when a token's book balance is compared with the actual balance
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reconciled {
+    #[topic]
+    pub token: Address,
+    pub book: i128,
+    pub actual: i128,
+}
+
+/// Published when a non-treasury token is swept out of the contract
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Swept {
+    #[topic]
+    pub token: Address,
+    pub to: Address,
+    pub amount: i128,
+}
+
+/// A budgeting period with per-category allocations, in units of its token.
+/// Categories without an allocation share what is left of the total budget.
+#[derive(Clone)]
+#[contracttype]
+pub struct BudgetEpoch {
+    pub id: u32,
+    pub token: Address,
+    pub start: u64,
+    pub end: u64,
+    pub total_budget: i128,
+    pub allocations: Map<Symbol, i128>,
+    pub carried: Map<Symbol, i128>,   // Unspent allocations rolled over from the previous epoch
+    pub spent: Map<Symbol, i128>,     // Spending against category allocations
+    pub reserve_spent: i128,          // Spending by categories without an allocation
+    pub rollover: bool,               // Whether unspent allocations carry into the next epoch
+    pub settled: bool,                // Whether carry-over from the previous epoch was applied
+}
+
+/// Published when a hospital anchors a new supporting document
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AttachmentAdded {
+    #[topic]
+    pub proposal_id: u64,
+    pub version: u32,
+    pub kind: AttachmentKind,
+    pub hash: BytesN<32>,
+}
+
+/// Published when a hospital amends a proposal; members whose votes were
+/// reset need to vote again
+#[contractevent]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProposalAmended {
+    #[topic]
+    pub proposal_id: u64,
+    pub amount_requested: i128,
+    pub votes_reset: u32,
+}
+
+/// Member motion to wind the DAO down
+#[derive(Clone)]
+#[contracttype]
+pub struct Dissolution {
+    pub id: u32,
+    pub proposer: Address,
+    pub grace_period: u64,
+    pub grace_ends: u64,    // Set once the motion is approved
+    pub votes_for: u32,
+    pub votes_against: u32,
+    pub status: DissolutionStatus,
+}
+
+/// Funds shared out on dissolution to the donors of one pot of a token:
+/// general funds or a restricted sub-balance. Each donor claims their part.
+#[derive(Clone)]
+#[contracttype]
+pub struct Distribution {
+    pub amount: i128,   // Total shared out so far
+    pub shares: i128,   // Sum of the donors' shares of the pot
+}
+
+/// Sponsor funds that match eligible donations and contributions.
+/// A pool's optional restriction is stored under `DataKey::PoolRestriction`.
+#[derive(Clone)]
+#[contracttype]
+pub struct MatchingPool {
+    pub id: u64,
+    pub sponsor: Address,
+    pub token: Address,
+    pub ratio_bps: u32,     // Match per donated unit, 10_000 = 1:1
+    pub cap: i128,
+    pub remaining: i128,
+    pub expires_at: u64,
+}
+
+/// Receipt of a donation, kept by the DAO for the donor's tax documentation.
+/// Receipts are records in contract storage and cannot be transferred.
+#[derive(Clone)]
+#[contracttype]
+pub struct Donation {
+    pub id: u64,
+    pub donor: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub timestamp: u64,
+}
+
+/// Funds credited to a payee, waiting to be claimed
+#[derive(Clone)]
+#[contracttype]
+pub struct ClaimableBalance {
+    pub amount: i128,
+    pub expires_at: u64,    // Extended, never shortened, each time the payee is credited
 }
 
 #[derive(Clone)]
@@ -34,9 +445,115 @@ pub enum DataKey {
     Proposal(u64),
     Vote(u64, Address),       // (proposal_id, voter_address)
     VotingThreshold,
-    Treasury,
+    Treasury(Address),        // Balance per whitelisted token contract
+    TreasuryToken(Address),   // Whitelisted treasury assets
+    TreasuryTokens,
     DAOMember(Address),
+    Members,                  // Every address added as a member
     AuthorizedHospital,       // Single authorized hospital address
+    ApprovedAmounts(u64),     // Sorted amounts proposed by approving voters
+    Milestones(u64),
+    MilestoneConfirmation(u64, u32, Address),  // (proposal_id, milestone_index, confirmer)
+    MilestoneConfirmations,   // Member confirmations needed to release a milestone
+    MilestoneReviewer,        // Optional reviewer whose confirmation alone releases a milestone
+    StreamDuration(u64),      // Requested vesting duration, set before execution
+    Stream(u64),
+    HaltVote(u64, Address),   // (proposal_id, member) votes to halt a stream
+    Provider(Address),        // Registered providers allowed as payees
+    Claimable(Address, Address),  // Unclaimed payouts per (recipient, token)
+    ClaimPeriod,              // Seconds a recipient has to claim before funds return
+    Oracle,                   // Price oracle used to settle fiat proposals
+    Receipt(ReceiptKey),
+    Crowdfund(u64),           // Contributions escrowed per proposal
+    Contribution(u64, Address),  // (proposal_id, donor) contribution amount
+    ProposalLifetime,         // Seconds before an unexecuted proposal expires (0 = never)
+    PoolCount,
+    Pool(u64),
+    PoolRestriction(u64),     // Restriction a matching pool is limited to
+    ActivePools,              // Ids of pools that have not been closed
+    Dissolution,              // Latest dissolution motion
+    DissolutionVote(u32, Address),  // (motion_id, member)
+    Holdings(Address),        // Tokens the DAO should hold per token, whatever they are earmarked for
+    Vault(Address),           // Yield vault configuration per token
+    RetentionPeriod,          // Seconds patient data is kept after a proposal closes
+    PatientCaps(Denomination),
+    PatientProposals(BytesN<32>),  // Proposal ids per patient id commitment
+    Attachments(u64),
+    VoteAttachments(u64, Address),  // Attachment version a member voted on
+    EncryptionKey(Address),   // Public key members receive case file keys under
+    Envelopes(u64),           // Case file key encrypted per member, per proposal
+    EpochCount(Address),      // Budget epochs planned per token
+    BudgetEpoch(Address, u32),
+    Fund(FundKey),
+    Review(ReviewKey),
+}
+
+/// Pot a proposal's payout was drawn from
+#[derive(Clone)]
+#[contracttype]
+pub enum FundSource {
+    Crowdfund,
+    Restricted(Restriction),
+    General,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub struct FundDraw {
+    pub source: FundSource,
+    pub amount: i128,
+}
+
+/// How a payout was funded, so escrow that is not released can go back
+#[derive(Clone)]
+#[contracttype]
+pub struct Funding {
+    pub epoch: Option<u32>,   // Budget epoch the payout was charged to
+    pub draws: Vec<FundDraw>, // In the order the pots were drawn from
+}
+
+/// Storage keys of donation receipts, stored under `DataKey::Receipt`.
+/// Counters live in instance storage, the records in persistent storage.
+#[derive(Clone)]
+#[contracttype]
+pub enum ReceiptKey {
+    DonationCount,
+    DonorCount,
+    Donation(u64),            // Donation receipts by id
+    Donor(u32),               // Donors by number, in order of their first donation
+    DonorTotal(Address, Address),  // Lifetime total per (donor, token)
+    DonorDonations(Address),  // Receipt ids per donor
+}
+
+/// Storage keys of restricted funds and the donors they came from, stored under `DataKey::Fund`
+#[derive(Clone)]
+#[contracttype]
+pub enum FundKey {
+    Restricted(Address, Restriction),  // Restricted sub-balance per (token, restriction)
+    RestrictedFunds(Address), // Restrictions holding funds, per token
+    Earmarks(Address, Restriction),  // Amount per donor of a (token, restriction) sub-balance
+    GeneralDonations(Address),  // Unrestricted donations per donor, per token
+    EscrowFunding(u64),       // Where a proposal's escrowed funds were drawn from
+    Distribution(Address),    // Dissolution payout of general funds, per token
+    DistributionClaimed(Address, Address),  // Paid out of it per (token, donor)
+    EarmarkDistribution(Address, Restriction),  // Dissolution payout of a (token, restriction) sub-balance
+    EarmarkClaimed(Address, Restriction, Address),  // Paid out of it per (token, restriction, donor)
+}
+
+/// Storage keys of the medical review of proposals and member voting, stored under `DataKey::Review`
+#[derive(Clone)]
+#[contracttype]
+pub enum ReviewKey {
+    Doctor(Address),
+    AttestationRule,
+    Attestations(u64),        // Medical-necessity attestations per proposal
+    Pipeline,                 // Review stages proposals pass before member voting
+    Role(Address, ReviewRole),
+    Review(u64),              // Review progress per proposal
+    CommitteeMode,            // Random voting committees, when enabled
+    Conflict(Address, Address),  // (member, hospital) conflicts of interest
+    Voters(u64),              // Members who voted on a proposal
+    AppealRule,
 }
 
 #[contract]
@@ -62,7 +579,6 @@ impl EmergencyFundDAO {
         env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage().instance().set(&DataKey::VotingThreshold, &voting_threshold);
         env.storage().instance().set(&DataKey::ProposalCount, &0u64);
-        env.storage().instance().set(&DataKey::Treasury, &0i128);
         env.storage().instance().set(&DataKey::AuthorizedHospital, &authorized_hospital);
     }
     
@@ -75,223 +591,3427 @@ impl EmergencyFundDAO {
             panic!("Only admin can add members");
         }
         
+        if !env.storage().instance().has(&DataKey::DAOMember(member.clone())) {
+            let mut members: Vec<Address> = env.storage().instance().get(&DataKey::Members).unwrap_or(Vec::new(&env));
+            members.push_back(member.clone());
+            env.storage().instance().set(&DataKey::Members, &members);
+        }
         env.storage().instance().set(&DataKey::DAOMember(member.clone()), &true);
     }
     
-    /// Add funds to the DAO treasury
-    pub fn add_funds(env: Env, amount: i128) {
-        if amount <= 0 {
-            panic!("Amount must be positive");
+    /// Register the public key a member receives case file keys under
+    pub fn set_encryption_key(env: Env, member: Address, public_key: BytesN<32>) {
+        member.require_auth();
+        
+        let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(member.clone())).unwrap_or(false);
+        if !is_member {
+            panic!("Only DAO members can register encryption keys");
         }
         
-        let mut treasury: i128 = env.storage().instance().get(&DataKey::Treasury).unwrap_or(0);
-        treasury += amount;
-        env.storage().instance().set(&DataKey::Treasury, &treasury);
+        env.storage().instance().set(&DataKey::EncryptionKey(member), &public_key);
     }
     
-    /// Submit a new proposal (only authorized hospital can do this)
-    pub fn submit_proposal(
-        env: Env,
-        hospital: Address,
-        patient_name: String,
-        patient_details: String,
-        amount_requested: i128,
-    ) -> u64 {
+    /// Get a member's registered encryption public key
+    pub fn get_encryption_key(env: Env, member: Address) -> BytesN<32> {
+        env.storage().instance()
+            .get(&DataKey::EncryptionKey(member))
+            .unwrap_or_else(|| panic!("Member has no encryption key"))
+    }
+    
+    /// Give a member the case file key of a proposal, e.g. after they joined
+    /// (submitting hospital only)
+    pub fn share_envelope(env: Env, hospital: Address, proposal_id: u64, member: Address, envelope: Bytes) {
         hospital.require_auth();
         
-        // Check if hospital is authorized
-        let authorized_hospital: Address = env.storage().instance()
-            .get(&DataKey::AuthorizedHospital)
-            .unwrap_or_else(|| panic!("Authorized hospital not set"));
-        
-        if hospital != authorized_hospital {
-            panic!("Only the authorized hospital can submit proposals");
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        if hospital != proposal.hospital {
+            panic!("Only the submitting hospital can share envelopes");
         }
-        
-        if amount_requested <= 0 {
-            panic!("Amount must be positive");
+        if proposal.tombstone.is_some() {
+            panic!("Patient data was erased");
         }
+        require_encryption_key(&env, &member);
         
-        // Get and increment proposal count
-        let mut proposal_count: u64 = env.storage().instance().get(&DataKey::ProposalCount).unwrap_or(0);
-        proposal_count += 1;
-        
-        // Create new proposal
-        let proposal = Proposal {
-            id: proposal_count,
-            hospital: hospital.clone(),
-            patient_name,
-            patient_details,
-            amount_requested,
-            votes_for: 0,
-            votes_against: 0,
-            status: ProposalStatus::Active,
-            created_at: env.ledger().timestamp(),
-        };
+        let mut envelopes: Map<Address, Bytes> = env.storage().instance()
+            .get(&DataKey::Envelopes(proposal_id))
+            .unwrap_or(Map::new(&env));
+        envelopes.set(member, envelope);
+        env.storage().instance().set(&DataKey::Envelopes(proposal_id), &envelopes);
+    }
+    
+    /// Get the case file key of a proposal encrypted for `member`
+    pub fn get_my_envelope(env: Env, proposal_id: u64, member: Address) -> Bytes {
+        let envelopes: Map<Address, Bytes> = env.storage().instance()
+            .get(&DataKey::Envelopes(proposal_id))
+            .unwrap_or(Map::new(&env));
+        envelopes.get(member).unwrap_or_else(|| panic!("No envelope for member"))
+    }
+    
+    /// Anchor a supporting document to an active proposal (submitting hospital only)
+    /// Returns the new attachment version.
+    pub fn add_attachment(
+        env: Env,
+        hospital: Address,
+        proposal_id: u64,
+        kind: AttachmentKind,
+        hash: BytesN<32>,
+        uri: Option<String>,
+    ) -> u32 {
+        hospital.require_auth();
         
-        // Store proposal
-        env.storage().instance().set(&DataKey::Proposal(proposal_count), &proposal);
-        env.storage().instance().set(&DataKey::ProposalCount, &proposal_count);
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        if hospital != proposal.hospital {
+            panic!("Only the submitting hospital can add attachments");
+        }
+        match proposal.status {
+            ProposalStatus::Active => {},
+            _ => panic!("Proposal is not active"),
+        }
         
-        proposal_count
+        push_attachment(&env, proposal_id, kind, hash, uri)
     }
     
-    /// Vote on a proposal (only DAO members can vote)
-    /// approve: true to vote for, false to vote against
-    pub fn vote(env: Env, voter: Address, proposal_id: u64, approve: bool) {
-        voter.require_auth();
-        
-        // Check if voter is a DAO member
-        let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(voter.clone())).unwrap_or(false);
-        if !is_member {
-            panic!("Only DAO members can vote");
+    /// Get a proposal's attachments as of `version` (0 for the latest)
+    pub fn get_attachments(env: Env, proposal_id: u64, version: u32) -> Vec<Attachment> {
+        let attachments: Vec<Attachment> = env.storage().instance()
+            .get(&DataKey::Attachments(proposal_id))
+            .unwrap_or(Vec::new(&env));
+        if version == 0 || version >= attachments.len() {
+            return attachments;
         }
+        attachments.slice(0..version)
+    }
+    
+    /// Get the attachment version that existed when a member voted
+    pub fn get_vote_attachment_version(env: Env, proposal_id: u64, voter: Address) -> u32 {
+        env.storage().instance()
+            .get(&DataKey::VoteAttachments(proposal_id, voter))
+            .unwrap_or_else(|| panic!("Member has not voted"))
+    }
+    
+    /// Register a licensed doctor (admin, or the authorized hospital for its own doctors)
+    pub fn register_doctor(env: Env, caller: Address, doctor: Address, hospital: Address, license_hash: BytesN<32>) {
+        caller.require_auth();
         
-        // Check if already voted
-        if env.storage().instance().has(&DataKey::Vote(proposal_id, voter.clone())) {
-            panic!("Already voted on this proposal");
+        // The authorized hospital registers its own doctors; admin registers anyone's
+        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if caller != admin {
+            let authorized_hospital: Address = env.storage().instance()
+                .get(&DataKey::AuthorizedHospital)
+                .unwrap_or_else(|| panic!("Authorized hospital not set"));
+            if caller != authorized_hospital || hospital != caller {
+                panic!("Only admin or the doctor's hospital can register doctors");
+            }
         }
         
-        // Get proposal
-        let mut proposal: Proposal = env.storage().instance()
-            .get(&DataKey::Proposal(proposal_id))
-            .unwrap_or_else(|| panic!("Proposal not found"));
+        env.storage().instance().set(&DataKey::Review(ReviewKey::Doctor(doctor)), &Doctor { hospital, license_hash });
+    }
+    
+    /// Remove a doctor from the registry (admin, or the doctor's hospital)
+    pub fn remove_doctor(env: Env, caller: Address, doctor: Address) {
+        caller.require_auth();
         
-        // Check if proposal is still active (not executed or permanently closed)
-        match proposal.status {
-            ProposalStatus::Active | ProposalStatus::Approved | ProposalStatus::Rejected => {},
-            ProposalStatus::Executed => panic!("Proposal already executed"),
+        let registered: Doctor = env.storage().instance()
+            .get(&DataKey::Review(ReviewKey::Doctor(doctor.clone())))
+            .unwrap_or_else(|| panic!("Doctor not registered"));
+        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if caller != admin && caller != registered.hospital {
+            panic!("Only admin or the doctor's hospital can remove doctors");
         }
         
-        // Record vote
-        if approve {
-            proposal.votes_for += 1;
-        } else {
-            proposal.votes_against += 1;
+        env.storage().instance().remove(&DataKey::Review(ReviewKey::Doctor(doctor)));
+    }
+    
+    /// Get a registered doctor
+    pub fn get_doctor(env: Env, doctor: Address) -> Doctor {
+        env.storage().instance()
+            .get(&DataKey::Review(ReviewKey::Doctor(doctor)))
+            .unwrap_or_else(|| panic!("Doctor not registered"))
+    }
+    
+    /// Require attestations before members can vote on a proposal (admin only)
+    /// required: number of attestations, 0 to let voting open immediately
+    /// independent_only: only count doctors from a different hospital
+    pub fn set_attestation_rule(env: Env, admin: Address, required: u32, independent_only: bool) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can set the attestation rule");
         }
         
-        // Store vote and updated proposal
-        env.storage().instance().set(&DataKey::Vote(proposal_id, voter), &approve);
-        env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+        let rule = AttestationRule { required, independent_only };
+        env.storage().instance().set(&DataKey::Review(ReviewKey::AttestationRule), &rule);
     }
     
-    /// Finalize voting on a proposal to determine if it's approved or rejected
-    pub fn finalize_proposal(env: Env, proposal_id: u64) {
-        // Get proposal
+    /// Attest that an active proposal's treatment is medically necessary (registered doctors only)
+    pub fn attest_medical_necessity(env: Env, doctor: Address, proposal_id: u64, statement_hash: BytesN<32>) {
+        doctor.require_auth();
+        
+        let registered: Doctor = env.storage().instance()
+            .get(&DataKey::Review(ReviewKey::Doctor(doctor.clone())))
+            .unwrap_or_else(|| panic!("Doctor not registered"));
         let mut proposal: Proposal = env.storage().instance()
             .get(&DataKey::Proposal(proposal_id))
             .unwrap_or_else(|| panic!("Proposal not found"));
-        
-        // Only active proposals can be finalized
         match proposal.status {
             ProposalStatus::Active => {},
             _ => panic!("Proposal is not active"),
         }
         
-        // Check if voting threshold is met
-        let voting_threshold: u32 = env.storage().instance().get(&DataKey::VotingThreshold).unwrap();
-        let total_votes = proposal.votes_for + proposal.votes_against;
-        
-        if total_votes < 3 {
-            panic!("Not enough votes to finalize (minimum 3 required)");
+        let mut attestations: Vec<Attestation> = env.storage().instance()
+            .get(&DataKey::Review(ReviewKey::Attestations(proposal_id)))
+            .unwrap_or(Vec::new(&env));
+        if attestations.iter().any(|attestation| attestation.doctor == doctor) {
+            panic!("Doctor already attested");
         }
         
-        let approval_percentage = (proposal.votes_for * 100) / total_votes;
+        attestations.push_back(Attestation {
+            doctor,
+            statement_hash,
+            independent: registered.hospital != proposal.hospital,
+            attested_at: env.ledger().timestamp(),
+        });
+        env.storage().instance().set(&DataKey::Review(ReviewKey::Attestations(proposal_id)), &attestations);
         
-        // Determine final status
-        if approval_percentage >= voting_threshold {
-            proposal.status = ProposalStatus::Approved;
-        } else {
-            proposal.status = ProposalStatus::Rejected;
+        proposal.attestations += 1;
+        env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+        if proposal.committee_deadline == 0 {
+            start_committee_vote(&env, proposal_id);
+        }
+    }
+    
+    /// Get the medical-necessity attestations of a proposal
+    pub fn get_attestations(env: Env, proposal_id: u64) -> Vec<Attestation> {
+        env.storage().instance()
+            .get(&DataKey::Review(ReviewKey::Attestations(proposal_id)))
+            .unwrap_or(Vec::new(&env))
+    }
+    
+    /// Have a random committee of `size` members vote on each new proposal
+    /// instead of every member (admin only). Size 0 turns committees off.
+    /// vote_period: seconds committee members have to vote before they can be replaced
+    pub fn set_committee_mode(env: Env, admin: Address, size: u32, vote_period: u64) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can set committee mode");
+        }
+        
+        if size == 0 {
+            env.storage().instance().remove(&DataKey::Review(ReviewKey::CommitteeMode));
+            return;
+        }
+        if size < MIN_VOTES {
+            panic!("Committee must have at least 3 members");
+        }
+        env.storage().instance().set(&DataKey::Review(ReviewKey::CommitteeMode), &CommitteeMode { size, vote_period });
+    }
+    
+    /// Declare a conflict of interest with a hospital; the member is left out
+    /// of that hospital's committees
+    pub fn declare_conflict(env: Env, member: Address, hospital: Address) {
+        member.require_auth();
+        
+        let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(member.clone())).unwrap_or(false);
+        if !is_member {
+            panic!("Only DAO members can declare conflicts");
+        }
+        
+        env.storage().instance().set(&DataKey::Review(ReviewKey::Conflict(member, hospital)), &true);
+    }
+    
+    /// Replace committee members who have not voted within the vote period
+    /// with randomly drawn members (anyone can call). Returns how many were replaced.
+    /// Appeals are never handed to members of the original decision.
+    pub fn replace_absent_members(env: Env, proposal_id: u64) -> u32 {
+        let mut proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        match proposal.status {
+            ProposalStatus::Active => {},
+            _ => panic!("Proposal is not active"),
         }
+        if proposal.committee.is_empty() {
+            panic!("Proposal has no committee");
+        }
+        if !is_voting_open(&env, &proposal) {
+            panic!("Proposal is not open for voting");
+        }
+        if env.ledger().timestamp() <= proposal.committee_deadline {
+            panic!("Committee vote period is still open");
+        }
+        
+        let mut present = Vec::new(&env);
+        let mut absent = Vec::new(&env);
+        for member in proposal.committee.iter() {
+            if env.storage().instance().has(&DataKey::Vote(proposal_id, member.clone())) {
+                present.push_back(member);
+            } else {
+                absent.push_back(member);
+            }
+        }
+        
+        let mut exclude = appeal_exclusions(&env, proposal.appeal_of);
+        exclude.append(&proposal.committee);
+        let replacements = draw_committee(&env, &proposal.hospital, &proposal.line_items, &exclude, absent.len());
+        let replaced = replacements.len();
+        present.append(&replacements);
+        // Absent members stay on when nobody is left to replace them
+        present.append(&absent.slice(replaced..));
         
+        let mode: Option<CommitteeMode> = env.storage().instance().get(&DataKey::Review(ReviewKey::CommitteeMode));
+        let vote_period = mode.map(|mode| mode.vote_period).unwrap_or(0);
+        proposal.committee = present;
+        proposal.committee_deadline = env.ledger().timestamp() + vote_period;
         env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+        
+        replaced
     }
     
-    /// Execute an approved proposal and release funds
-    pub fn execute_proposal(env: Env, proposal_id: u64) {
-        // Get proposal
+    /// Send new proposals through triage and medical review before member
+    /// voting (admin only). Each stage must finish within its period.
+    pub fn set_review_pipeline(
+        env: Env,
+        admin: Address,
+        triage_period: u64,
+        review_period: u64,
+        voting_period: u64,
+        reviews_required: u32,
+    ) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can set the review pipeline");
+        }
+        
+        if triage_period == 0 || review_period == 0 || voting_period == 0 {
+            panic!("Stage periods must be positive");
+        }
+        if reviews_required == 0 {
+            panic!("At least one review is required");
+        }
+        
+        let pipeline = ReviewPipeline { triage_period, review_period, voting_period, reviews_required };
+        env.storage().instance().set(&DataKey::Review(ReviewKey::Pipeline), &pipeline);
+    }
+    
+    /// Grant or revoke a coordinator or reviewer role (admin only)
+    pub fn set_review_role(env: Env, admin: Address, account: Address, role: ReviewRole, granted: bool) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can assign review roles");
+        }
+        
+        if granted {
+            env.storage().instance().set(&DataKey::Review(ReviewKey::Role(account, role)), &true);
+        } else {
+            env.storage().instance().remove(&DataKey::Review(ReviewKey::Role(account, role)));
+        }
+    }
+    
+    /// Pass a proposal in triage to medical review, or return it to the
+    /// hospital as incomplete (coordinators only)
+    pub fn triage_proposal(env: Env, coordinator: Address, proposal_id: u64, complete: bool) {
+        coordinator.require_auth();
+        require_review_role(&env, &coordinator, ReviewRole::Coordinator);
+        
+        let mut review = load_review_stage(&env, proposal_id, ReviewStage::Triage);
+        if complete {
+            let pipeline: ReviewPipeline = env.storage().instance().get(&DataKey::Review(ReviewKey::Pipeline)).unwrap();
+            review.stage = ReviewStage::MedicalReview;
+            review.deadline = env.ledger().timestamp() + pipeline.review_period;
+        } else {
+            review.stage = ReviewStage::Returned;
+        }
+        env.storage().instance().set(&DataKey::Review(ReviewKey::Review(proposal_id)), &review);
+    }
+    
+    /// Approve a proposal in medical review or request changes (reviewers only)
+    /// Voting opens once the required number of reviewers approve.
+    pub fn review_proposal(env: Env, reviewer: Address, proposal_id: u64, approve: bool) {
+        reviewer.require_auth();
+        require_review_role(&env, &reviewer, ReviewRole::Reviewer);
+        
+        let mut review = load_review_stage(&env, proposal_id, ReviewStage::MedicalReview);
+        if !approve {
+            review.stage = ReviewStage::Returned;
+            env.storage().instance().set(&DataKey::Review(ReviewKey::Review(proposal_id)), &review);
+            return;
+        }
+        
+        if review.approved_by.contains(&reviewer) {
+            panic!("Reviewer already approved");
+        }
+        review.approved_by.push_back(reviewer);
+        
+        let pipeline: ReviewPipeline = env.storage().instance().get(&DataKey::Review(ReviewKey::Pipeline)).unwrap();
+        if review.approved_by.len() >= pipeline.reviews_required {
+            review.stage = ReviewStage::Voting;
+            review.deadline = env.ledger().timestamp() + pipeline.voting_period;
+        }
+        env.storage().instance().set(&DataKey::Review(ReviewKey::Review(proposal_id)), &review);
+        start_committee_vote(&env, proposal_id);
+    }
+    
+    /// Revise a returned proposal, or one stuck past a triage or review
+    /// deadline, and send it back to triage (submitting hospital only)
+    /// The patient cannot change; envelopes for the new case file can be
+    /// re-shared with `share_envelope`.
+    pub fn revise_proposal(env: Env, hospital: Address, proposal_id: u64, case_file: CaseFile, line_items: Vec<LineItem>) {
+        hospital.require_auth();
+        
         let mut proposal: Proposal = env.storage().instance()
             .get(&DataKey::Proposal(proposal_id))
             .unwrap_or_else(|| panic!("Proposal not found"));
+        if hospital != proposal.hospital {
+            panic!("Only the submitting hospital can revise a proposal");
+        }
+        if proposal.tombstone.is_some() {
+            panic!("Patient data was erased");
+        }
+        match proposal.status {
+            ProposalStatus::Active => {},
+            _ => panic!("Proposal is not active"),
+        }
+        
+        let review: Review = env.storage().instance()
+            .get(&DataKey::Review(ReviewKey::Review(proposal_id)))
+            .unwrap_or_else(|| panic!("Proposal is not under review"));
+        let overdue = env.ledger().timestamp() > review.deadline;
+        let revisable = match review.stage {
+            ReviewStage::Returned => true,
+            ReviewStage::Triage | ReviewStage::MedicalReview => overdue,
+            ReviewStage::Voting => false,
+        };
+        if !revisable {
+            panic!("Proposal cannot be revised at this stage");
+        }
+        
+        apply_revision(&env, &mut proposal, case_file, line_items);
+        env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+        
+        start_review(&env, proposal_id, review.round + 1);
+        start_committee_vote(&env, proposal_id);
+    }
+    
+    /// Correct an active proposal (submitting hospital only)
+    /// Once votes are cast, amending requires `reset_votes`: every vote is
+    /// discarded and a `ProposalAmended` event tells members to vote again.
+    /// Proposals under review go back to triage.
+    pub fn amend_proposal(
+        env: Env,
+        hospital: Address,
+        proposal_id: u64,
+        case_file: CaseFile,
+        line_items: Vec<LineItem>,
+        reset_votes: bool,
+    ) {
+        hospital.require_auth();
         
-        // Check if proposal is approved
+        let mut proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        if hospital != proposal.hospital {
+            panic!("Only the submitting hospital can amend a proposal");
+        }
         match proposal.status {
-            ProposalStatus::Approved => {},
-            ProposalStatus::Executed => panic!("Proposal already executed"),
-            ProposalStatus::Rejected => panic!("Proposal was rejected"),
-            ProposalStatus::Active => panic!("Proposal is still active, not enough votes"),
+            ProposalStatus::Active => {},
+            _ => panic!("Proposal is not active"),
         }
         
-        // Check if treasury has enough funds
-        let mut treasury: i128 = env.storage().instance().get(&DataKey::Treasury).unwrap_or(0);
-        if treasury < proposal.amount_requested {
-            panic!("Insufficient funds in treasury");
+        let votes_reset = proposal.votes_for + proposal.votes_against;
+        if votes_reset > 0 && !reset_votes {
+            panic!("Proposal already has votes");
         }
         
-        // Deduct from treasury
-        treasury -= proposal.amount_requested;
-        env.storage().instance().set(&DataKey::Treasury, &treasury);
+        apply_revision(&env, &mut proposal, case_file, line_items);
+        if votes_reset > 0 {
+            clear_votes(&env, &mut proposal);
+        }
+        env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
         
-        // Mark proposal as executed
-        proposal.status = ProposalStatus::Executed;
+        if let Some(review) = env.storage().instance().get::<_, Review>(&DataKey::Review(ReviewKey::Review(proposal_id))) {
+            start_review(&env, proposal_id, review.round + 1);
+        }
+        start_committee_vote(&env, proposal_id);
+        
+        ProposalAmended { proposal_id, amount_requested: proposal.amount_requested, votes_reset }.publish(&env);
+    }
+    
+    /// Withdraw a proposal that has not been paid (submitting hospital only),
+    /// e.g. when the patient's insurance covers the treatment
+    /// Contributions become refundable and funds donated to this proposal
+    /// are credited back to their donors as claims.
+    pub fn withdraw_proposal(env: Env, hospital: Address, proposal_id: u64) {
+        hospital.require_auth();
+        
+        let mut proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        if hospital != proposal.hospital {
+            panic!("Only the submitting hospital can withdraw a proposal");
+        }
+        match proposal.status {
+            ProposalStatus::Active | ProposalStatus::Approved => {},
+            _ => panic!("Only pending proposals can be withdrawn"),
+        }
+        
+        release_earmark(&env, &proposal.token, &Restriction::Proposal(proposal_id));
+        
+        proposal.status = ProposalStatus::Cancelled;
+        proposal.closed_at = env.ledger().timestamp();
         env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+    }
+    
+    /// Set the appeal window and the approval percentage appeals need (admin only)
+    pub fn set_appeal_rule(env: Env, admin: Address, window: u64, threshold: u32) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can set the appeal rule");
+        }
+        let voting_threshold: u32 = env.storage().instance().get(&DataKey::VotingThreshold).unwrap();
+        if threshold <= voting_threshold || threshold > 100 {
+            panic!("Appeal threshold must be above the voting threshold and at most 100");
+        }
         
-        // NOTE: In a production system, this is where you would transfer actual tokens
-        // from the DAO contract to proposal.hospital using a token contract.
-        // For this MVP, we only track the balance changes.
+        env.storage().instance().set(&DataKey::Review(ReviewKey::AppealRule), &AppealRule { window, threshold });
     }
     
-    /// Get proposal details by ID
-    pub fn get_proposal(env: Env, proposal_id: u64) -> Proposal {
-        env.storage().instance()
+    /// Get the appeal rule
+    pub fn get_appeal_rule(env: Env) -> AppealRule {
+        appeal_rule(&env)
+    }
+    
+    /// Appeal a rejected proposal with new evidence (submitting hospital only,
+    /// within the appeal window). Returns the id of the appeal, a new proposal
+    /// linked to the original that needs the appeal threshold to pass. With
+    /// committee mode on, its committee excludes the original's voters.
+    pub fn appeal_proposal(
+        env: Env,
+        hospital: Address,
+        proposal_id: u64,
+        case_file: CaseFile,
+        line_items: Vec<LineItem>,
+        evidence: Vec<BytesN<32>>,
+        envelopes: Map<Address, Bytes>,
+    ) -> u64 {
+        let mut original: Proposal = env.storage().instance()
             .get(&DataKey::Proposal(proposal_id))
-            .unwrap_or_else(|| panic!("Proposal not found"))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        if hospital != original.hospital {
+            panic!("Only the submitting hospital can appeal a proposal");
+        }
+        match original.status {
+            ProposalStatus::Rejected => {},
+            _ => panic!("Only rejected proposals can be appealed"),
+        }
+        if original.appealed_by.is_some() {
+            panic!("Proposal was already appealed");
+        }
+        if env.ledger().timestamp() > original.closed_at.saturating_add(appeal_rule(&env).window) {
+            panic!("Appeal window has closed");
+        }
+        if env.storage().instance().has(&DataKey::Milestones(proposal_id)) {
+            panic!("Milestone proposals cannot be appealed");
+        }
+        if case_file.patient_id != original.case_file.patient_id {
+            panic!("Patient cannot change");
+        }
+        
+        // Evidence must not repeat what the original was decided on
+        if evidence.is_empty() {
+            panic!("Appeal needs new evidence");
+        }
+        let attachments: Vec<Attachment> = env.storage().instance()
+            .get(&DataKey::Attachments(proposal_id))
+            .unwrap_or(Vec::new(&env));
+        for hash in evidence.iter() {
+            if attachments.iter().any(|attachment| attachment.hash == hash) {
+                panic!("Evidence was already submitted");
+            }
+        }
+        
+        validate_line_items(&env, &line_items);
+        let appeal_id = create_proposal(
+            &env,
+            hospital,
+            case_file,
+            original.category.clone(),
+            original.token.clone(),
+            original.fiat_currency.clone(),
+            line_items,
+            envelopes,
+            Some(proposal_id),
+        );
+        for hash in evidence.iter() {
+            push_attachment(&env, appeal_id, AttachmentKind::AppealEvidence, hash, None);
+        }
+        
+        original.appealed_by = Some(appeal_id);
+        env.storage().instance().set(&DataKey::Proposal(proposal_id), &original);
+        appeal_id
     }
     
-    /// Get total number of proposals
-    pub fn get_proposal_count(env: Env) -> u64 {
-        env.storage().instance().get(&DataKey::ProposalCount).unwrap_or(0)
+    /// Get the ids of every proposal in a proposal's appeal chain, from the
+    /// original submission to the latest appeal
+    pub fn get_appeal_chain(env: Env, proposal_id: u64) -> Vec<u64> {
+        let mut proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        while let Some(original_id) = proposal.appeal_of {
+            proposal = env.storage().instance().get(&DataKey::Proposal(original_id)).unwrap();
+        }
+        
+        let mut chain = vec![&env, proposal.id];
+        while let Some(appeal_id) = proposal.appealed_by {
+            chain.push_back(appeal_id);
+            proposal = env.storage().instance().get(&DataKey::Proposal(appeal_id)).unwrap();
+        }
+        chain
     }
     
-    /// Get treasury balance
-    pub fn get_treasury_balance(env: Env) -> i128 {
-        env.storage().instance().get(&DataKey::Treasury).unwrap_or(0)
+    /// Get where a proposal is in the review pipeline
+    pub fn get_review(env: Env, proposal_id: u64) -> Review {
+        env.storage().instance()
+            .get(&DataKey::Review(ReviewKey::Review(proposal_id)))
+            .unwrap_or_else(|| panic!("Proposal is not under review"))
     }
     
-    /// Check if an address is a DAO member
-    pub fn is_member(env: Env, address: Address) -> bool {
-        env.storage().instance().get(&DataKey::DAOMember(address)).unwrap_or(false)
+    /// Whitelist a token contract as a treasury asset (admin only)
+    pub fn add_treasury_token(env: Env, admin: Address, token: Address) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can add treasury tokens");
+        }
+        
+        if env.storage().instance().has(&DataKey::TreasuryToken(token.clone())) {
+            panic!("Token already whitelisted");
+        }
+        
+        let mut tokens: Vec<Address> = env.storage().instance().get(&DataKey::TreasuryTokens).unwrap_or(Vec::new(&env));
+        tokens.push_back(token.clone());
+        env.storage().instance().set(&DataKey::TreasuryTokens, &tokens);
+        env.storage().instance().set(&DataKey::TreasuryToken(token), &true);
     }
     
-    /// Check if a voter has voted on a proposal
-    pub fn has_voted(env: Env, proposal_id: u64, voter: Address) -> bool {
-        env.storage().instance().has(&DataKey::Vote(proposal_id, voter))
+    /// Get a restricted sub-balance of the treasury
+    pub fn get_restricted_balance(env: Env, token: Address, restriction: Restriction) -> i128 {
+        restricted_balance(&env, &token, &restriction)
     }
     
-    /// Get voting threshold
-    pub fn get_voting_threshold(env: Env) -> u32 {
-        env.storage().instance().get(&DataKey::VotingThreshold).unwrap_or(0)
+    /// Get the restrictions that have received funds in a token
+    pub fn get_restricted_funds(env: Env, token: Address) -> Vec<Restriction> {
+        env.storage().instance().get(&DataKey::Fund(FundKey::RestrictedFunds(token))).unwrap_or(Vec::new(&env))
     }
     
-    /// Get authorized hospital address
-    pub fn get_authorized_hospital(env: Env) -> Address {
+    /// Refund funds donated to a proposal once it can no longer use them, e.g.
+    /// after it expired or was paid in full (anyone can call)
+    /// Donors receive their share as claims. Returns the amount refunded.
+    pub fn release_proposal_earmark(env: Env, proposal_id: u64) -> i128 {
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        let closed = match proposal.status {
+            ProposalStatus::Rejected | ProposalStatus::Cancelled | ProposalStatus::Executed => true,
+            _ => is_expired(&env, &proposal),
+        };
+        if !closed {
+            panic!("Proposal can still be paid");
+        }
+        
+        release_earmark(&env, &proposal.token, &Restriction::Proposal(proposal_id))
+    }
+    
+    /// Get the funds a proposal can currently be paid from (restricted and general)
+    pub fn get_available_funds(env: Env, proposal_id: u64) -> i128 {
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        available_funds(&env, &proposal)
+    }
+    
+    /// Contribute directly to a proposal (any donor)
+    /// Contributions are escrowed and used first when the proposal executes.
+    /// If it is rejected, cancelled or expires, contributors get a refund with
+    /// `refund_contribution`; any escrow left after execution is shared the same way.
+    pub fn contribute_to_proposal(env: Env, donor: Address, proposal_id: u64, amount: i128) {
+        donor.require_auth();
+        
+        if amount <= 0 {
+            panic!("Amount must be positive");
+        }
+        require_not_dissolving(&env);
+        
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        if is_funding_closed(&env, &proposal) {
+            panic!("Proposal is not accepting contributions");
+        }
+        
+        receive_tokens(&env, &proposal.token, &donor, amount);
+        escrow_contribution(&env, proposal_id, donor, amount);
+        
+        // Matched funds are escrowed as the sponsor's own contribution
+        for (sponsor, matched) in draw_matches(&env, &proposal.token, amount, None, Some(&proposal)).iter() {
+            escrow_contribution(&env, proposal_id, sponsor, matched);
+        }
+    }
+    
+    /// Refund a contributor's share of a closed proposal's remaining escrow
+    pub fn refund_contribution(env: Env, donor: Address, proposal_id: u64) -> i128 {
+        donor.require_auth();
+        
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        let key = DataKey::Contribution(proposal_id, donor.clone());
+        let contribution: i128 = env.storage().instance()
+            .get(&key)
+            .unwrap_or_else(|| panic!("No contribution to refund"));
+        
+        let mut crowdfund: Crowdfund = env.storage().instance().get(&DataKey::Crowdfund(proposal_id)).unwrap();
+        if !crowdfund.closed {
+            if !is_funding_closed(&env, &proposal) {
+                panic!("Proposal is still open for funding");
+            }
+            // Escrow that is not released yet may still come back to the crowdfund
+            if !is_funding_settled(&env, &proposal) {
+                panic!("Proposal funds are still in escrow");
+            }
+            crowdfund.closed = true;
+            crowdfund.refund_pool = crowdfund.balance;
+        }
+        
+        // Cumulative rounding so the refunds add up to the pool exactly
+        let paid_before = crowdfund.refund_pool * crowdfund.refunded / crowdfund.contributed;
+        crowdfund.refunded += contribution;
+        let paid_after = crowdfund.refund_pool * crowdfund.refunded / crowdfund.contributed;
+        let refund = paid_after - paid_before;
+        
+        crowdfund.balance -= refund;
+        env.storage().instance().set(&DataKey::Crowdfund(proposal_id), &crowdfund);
+        env.storage().instance().remove(&key);
+        
+        if refund > 0 {
+            send_tokens(&env, &proposal.token, &donor, refund);
+        }
+        refund
+    }
+    
+    /// Get the crowdfunding escrow of a proposal
+    pub fn get_crowdfund(env: Env, proposal_id: u64) -> Crowdfund {
         env.storage().instance()
-            .get(&DataKey::AuthorizedHospital)
-            .unwrap_or_else(|| panic!("Authorized hospital not set"))
+            .get(&DataKey::Crowdfund(proposal_id))
+            .unwrap_or(Crowdfund { contributed: 0, balance: 0, refund_pool: 0, refunded: 0, closed: false })
     }
     
-    /// Update authorized hospital (admin only)
-    pub fn set_authorized_hospital(env: Env, admin: Address, new_hospital: Address) {
+    /// Get a donor's outstanding contribution to a proposal
+    pub fn get_contribution(env: Env, proposal_id: u64, donor: Address) -> i128 {
+        env.storage().instance().get(&DataKey::Contribution(proposal_id, donor)).unwrap_or(0)
+    }
+    
+    /// Set how long a proposal stays open before it expires (admin only)
+    /// lifetime: seconds from submission; 0 disables expiry
+    pub fn set_proposal_lifetime(env: Env, admin: Address, lifetime: u64) {
         admin.require_auth();
         
         let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
         if admin != stored_admin {
-            panic!("Only admin can update authorized hospital");
+            panic!("Only admin can set the proposal lifetime");
         }
         
-        env.storage().instance().set(&DataKey::AuthorizedHospital, &new_hospital);
+        env.storage().instance().set(&DataKey::ProposalLifetime, &lifetime);
+    }
+    
+    /// Get the whitelisted treasury assets
+    pub fn get_treasury_tokens(env: Env) -> Vec<Address> {
+        env.storage().instance().get(&DataKey::TreasuryTokens).unwrap_or(Vec::new(&env))
+    }
+    
+    /// Compare the DAO's book balance of a treasury token with the tokens it
+    /// actually holds (anyone can call)
+    /// A surplus, e.g. from direct transfers to the contract, is added to
+    /// general funds without a donation receipt. Returns actual minus book.
+    pub fn reconcile(env: Env, token: Address) -> i128 {
+        require_treasury_token(&env, &token);
+        
+        let book = holdings(&env, &token);
+        let actual = token::Client::new(&env, &token).balance(&env.current_contract_address());
+        
+        let surplus = actual - book;
+        if surplus > 0 {
+            env.storage().instance().set(&DataKey::Holdings(token.clone()), &actual);
+            deposit_to_treasury(&env, &token, surplus);
+        }
+        
+        Reconciled { token, book, actual }.publish(&env);
+        surplus
+    }
+    
+    /// Send the whole balance of a token that is not a treasury asset to `to` (admin only)
+    pub fn sweep(env: Env, admin: Address, token: Address, to: Address) -> i128 {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can sweep tokens");
+        }
+        
+        if env.storage().instance().has(&DataKey::TreasuryToken(token.clone())) {
+            panic!("Cannot sweep a treasury asset");
+        }
+        
+        let amount = token::Client::new(&env, &token).balance(&env.current_contract_address());
+        if amount == 0 {
+            panic!("Nothing to sweep");
+        }
+        token::Client::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+        
+        Swept { token, to, amount }.publish(&env);
+        amount
+    }
+    
+    /// Add funds to the DAO treasury
+    /// Transfers `amount` of a whitelisted token from the donor to the DAO
+    /// and returns the id of the donation receipt.
+    /// restriction: optionally earmark the funds for a category, hospital or proposal
+    pub fn add_funds(env: Env, donor: Address, token: Address, amount: i128, restriction: Option<Restriction>) -> u64 {
+        donor.require_auth();
+        
+        if amount <= 0 {
+            panic!("Amount must be positive");
+        }
+        require_not_dissolving(&env);
+        require_treasury_token(&env, &token);
+        if let Some(Restriction::Proposal(proposal_id)) = restriction {
+            let proposal: Proposal = env.storage().instance()
+                .get(&DataKey::Proposal(proposal_id))
+                .unwrap_or_else(|| panic!("Proposal not found"));
+            if is_funding_closed(&env, &proposal) {
+                panic!("Proposal is not accepting contributions");
+            }
+        }
+        
+        receive_tokens(&env, &token, &donor, amount);
+        credit_donation(&env, &donor, &token, &restriction, amount);
+        let donation_id = record_donation(&env, donor, token.clone(), amount);
+        
+        // Matched funds follow the donation and are receipted to the sponsor
+        for (sponsor, matched) in draw_matches(&env, &token, amount, restriction.as_ref(), None).iter() {
+            credit_donation(&env, &sponsor, &token, &restriction, matched);
+            record_donation(&env, sponsor, token.clone(), matched);
+        }
+        
+        donation_id
+    }
+    
+    /// Create a matching pool (any sponsor)
+    /// The sponsor deposits `cap` up front; every eligible donation or proposal
+    /// contribution in `token` is matched at `ratio_bps` (10_000 = 1:1) until
+    /// the pool runs out or expires.
+    /// restriction: only match funds for this category, hospital or proposal
+    pub fn create_matching_pool(
+        env: Env,
+        sponsor: Address,
+        token: Address,
+        ratio_bps: u32,
+        cap: i128,
+        expires_at: u64,
+        restriction: Option<Restriction>,
+    ) -> u64 {
+        sponsor.require_auth();
+        
+        if cap <= 0 {
+            panic!("Amount must be positive");
+        }
+        if ratio_bps == 0 {
+            panic!("Match ratio must be positive");
+        }
+        if expires_at <= env.ledger().timestamp() {
+            panic!("Pool must expire in the future");
+        }
+        require_not_dissolving(&env);
+        require_treasury_token(&env, &token);
+        
+        receive_tokens(&env, &token, &sponsor, cap);
+        
+        let mut pool_count: u64 = env.storage().instance().get(&DataKey::PoolCount).unwrap_or(0);
+        pool_count += 1;
+        
+        let pool = MatchingPool {
+            id: pool_count,
+            sponsor,
+            token,
+            ratio_bps,
+            cap,
+            remaining: cap,
+            expires_at,
+        };
+        env.storage().instance().set(&DataKey::Pool(pool_count), &pool);
+        env.storage().instance().set(&DataKey::PoolCount, &pool_count);
+        if let Some(restriction) = restriction {
+            env.storage().instance().set(&DataKey::PoolRestriction(pool_count), &restriction);
+        }
+        
+        let mut active: Vec<u64> = env.storage().instance().get(&DataKey::ActivePools).unwrap_or(Vec::new(&env));
+        active.push_back(pool_count);
+        env.storage().instance().set(&DataKey::ActivePools, &active);
+        
+        pool_count
+    }
+    
+    /// Return what is left in an expired matching pool to its sponsor
+    pub fn close_matching_pool(env: Env, sponsor: Address, pool_id: u64) -> i128 {
+        sponsor.require_auth();
+        
+        let mut pool: MatchingPool = env.storage().instance()
+            .get(&DataKey::Pool(pool_id))
+            .unwrap_or_else(|| panic!("Matching pool not found"));
+        
+        if sponsor != pool.sponsor {
+            panic!("Only the sponsor can close a matching pool");
+        }
+        if env.ledger().timestamp() <= pool.expires_at {
+            panic!("Matching pool has not expired");
+        }
+        
+        let mut active: Vec<u64> = env.storage().instance().get(&DataKey::ActivePools).unwrap_or(Vec::new(&env));
+        match active.first_index_of(pool_id) {
+            Some(index) => active.remove(index),
+            None => panic!("Matching pool already closed"),
+        };
+        env.storage().instance().set(&DataKey::ActivePools, &active);
+        
+        let returned = pool.remaining;
+        pool.remaining = 0;
+        env.storage().instance().set(&DataKey::Pool(pool_id), &pool);
+        if returned > 0 {
+            send_tokens(&env, &pool.token, &sponsor, returned);
+        }
+        returned
+    }
+    
+    /// Get a matching pool by id
+    pub fn get_matching_pool(env: Env, pool_id: u64) -> MatchingPool {
+        env.storage().instance()
+            .get(&DataKey::Pool(pool_id))
+            .unwrap_or_else(|| panic!("Matching pool not found"))
+    }
+    
+    /// Get the matching capacity left in a pool (0 once expired)
+    pub fn get_pool_remaining(env: Env, pool_id: u64) -> i128 {
+        let pool: MatchingPool = env.storage().instance()
+            .get(&DataKey::Pool(pool_id))
+            .unwrap_or_else(|| panic!("Matching pool not found"));
+        if env.ledger().timestamp() > pool.expires_at {
+            return 0;
+        }
+        pool.remaining
+    }
+    
+    /// Get the ids of matching pools that have not been closed
+    pub fn get_active_pools(env: Env) -> Vec<u64> {
+        env.storage().instance().get(&DataKey::ActivePools).unwrap_or(Vec::new(&env))
+    }
+    
+    /// Propose dissolving the DAO (members only)
+    /// grace_period: seconds approved proposals have left to execute once
+    /// the motion passes
+    pub fn propose_dissolution(env: Env, proposer: Address, grace_period: u64) -> u32 {
+        proposer.require_auth();
+        
+        let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(proposer.clone())).unwrap_or(false);
+        if !is_member {
+            panic!("Only DAO members can propose dissolution");
+        }
+        
+        let mut id: u32 = 1;
+        if let Some(current) = env.storage().instance().get::<_, Dissolution>(&DataKey::Dissolution) {
+            match current.status {
+                DissolutionStatus::Rejected => id = current.id + 1,
+                _ => panic!("Dissolution already proposed"),
+            }
+        }
+        
+        let dissolution = Dissolution {
+            id,
+            proposer,
+            grace_period,
+            grace_ends: 0,
+            votes_for: 0,
+            votes_against: 0,
+            status: DissolutionStatus::Voting,
+        };
+        env.storage().instance().set(&DataKey::Dissolution, &dissolution);
+        
+        id
+    }
+    
+    /// Vote on the open dissolution motion (members only)
+    pub fn vote_dissolution(env: Env, voter: Address, approve: bool) {
+        voter.require_auth();
+        
+        let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(voter.clone())).unwrap_or(false);
+        if !is_member {
+            panic!("Only DAO members can vote");
+        }
+        
+        let mut dissolution = load_dissolution_motion(&env);
+        let key = DataKey::DissolutionVote(dissolution.id, voter);
+        if env.storage().instance().has(&key) {
+            panic!("Already voted on this motion");
+        }
+        
+        if approve {
+            dissolution.votes_for += 1;
+        } else {
+            dissolution.votes_against += 1;
+        }
+        env.storage().instance().set(&key, &approve);
+        env.storage().instance().set(&DataKey::Dissolution, &dissolution);
+    }
+    
+    /// Close voting on the dissolution motion
+    /// Once approved, no new proposals, donations or contributions are
+    /// accepted and open proposals expire when the grace period ends.
+    pub fn finalize_dissolution(env: Env) -> DissolutionStatus {
+        let mut dissolution = load_dissolution_motion(&env);
+        
+        let voting_threshold: u32 = env.storage().instance().get(&DataKey::VotingThreshold).unwrap();
+        let total_votes = dissolution.votes_for + dissolution.votes_against;
+        
+        if total_votes < MIN_VOTES {
+            panic!("Not enough votes to finalize (minimum 3 required)");
+        }
+        
+        if (dissolution.votes_for * 100) / total_votes >= voting_threshold {
+            dissolution.status = DissolutionStatus::Approved;
+            dissolution.grace_ends = env.ledger().timestamp() + dissolution.grace_period;
+        } else {
+            dissolution.status = DissolutionStatus::Rejected;
+        }
+        env.storage().instance().set(&DataKey::Dissolution, &dissolution);
+        
+        dissolution.status
+    }
+    
+    /// Refund the remaining treasury of a token to donors (anyone can call)
+    /// Each restricted sub-balance goes back to the donors who restricted it,
+    /// and general funds are shared pro rata to unrestricted donations. Donors
+    /// collect their part with `claim_distribution`, which never expires. Can be
+    /// called again for funds that return later, e.g. expired payouts.
+    pub fn distribute_remaining(env: Env, token: Address) -> i128 {
+        let grace_ends = dissolution_grace_end(&env)
+            .unwrap_or_else(|| panic!("DAO is not dissolving"));
+        if env.ledger().timestamp() <= grace_ends {
+            panic!("Grace period has not ended");
+        }
+        
+        // Bring invested funds and their yield back before sharing them out
+        if let Some(config) = vault_config(&env, &token) {
+            harvest_vault_yield(&env, &token);
+            recall_from_vault(&env, &token, config.principal);
+        }
+        
+        // Restricted funds go back to the donors who restricted them; those
+        // nobody restricted, e.g. sponsor matching, join general funds
+        let mut distributed: i128 = 0;
+        let restrictions: Vec<Restriction> = env.storage().instance()
+            .get(&DataKey::Fund(FundKey::RestrictedFunds(token.clone())))
+            .unwrap_or(Vec::new(&env));
+        for restriction in restrictions.iter() {
+            let balance = restricted_balance(&env, &token, &restriction);
+            if balance == 0 {
+                continue;
+            }
+            env.storage().instance().set(&DataKey::Fund(FundKey::Restricted(token.clone(), restriction.clone())), &0_i128);
+            if share_out(&env, &token, &Some(restriction), balance) {
+                distributed += balance;
+            } else {
+                deposit_to_treasury(&env, &token, balance);
+            }
+        }
+        
+        // General funds are shared by unrestricted donations, including any
+        // unattributed surplus `reconcile` added to them
+        let remaining = treasury_balance(&env, &token);
+        if remaining > 0 && share_out(&env, &token, &None, remaining) {
+            withdraw_from_treasury(&env, &token, remaining);
+            distributed += remaining;
+        }
+        
+        if distributed == 0 {
+            panic!("Nothing to distribute");
+        }
+        distributed
+    }
+    
+    /// Withdraw a donor's part of what `distribute_remaining` shared out of a
+    /// token's general funds (restriction None) or a restricted sub-balance
+    pub fn claim_distribution(env: Env, donor: Address, token: Address, restriction: Option<Restriction>) -> i128 {
+        donor.require_auth();
+        
+        let amount = distribution_owed(&env, &donor, &token, &restriction);
+        if amount == 0 {
+            panic!("Nothing to claim");
+        }
+        
+        let key = claimed_key(&token, &restriction, &donor);
+        let claimed: i128 = env.storage().instance().get(&key).unwrap_or(0);
+        env.storage().instance().set(&key, &(claimed + amount));
+        send_tokens(&env, &token, &donor, amount);
+        
+        amount
+    }
+    
+    /// Get what a donor can still claim of a dissolution payout
+    pub fn get_distribution_owed(env: Env, donor: Address, token: Address, restriction: Option<Restriction>) -> i128 {
+        distribution_owed(&env, &donor, &token, &restriction)
+    }
+    
+    /// Get the latest dissolution motion
+    pub fn get_dissolution(env: Env) -> Dissolution {
+        env.storage().instance()
+            .get(&DataKey::Dissolution)
+            .unwrap_or_else(|| panic!("No dissolution proposed"))
+    }
+    
+    /// Get a donor's lifetime donations of a token
+    pub fn get_donor_total(env: Env, donor: Address, token: Address) -> i128 {
+        get_record(&env, ReceiptKey::DonorTotal(donor, token)).unwrap_or(0)
+    }
+    
+    /// Get a donation receipt by id
+    pub fn get_donation(env: Env, donation_id: u64) -> Donation {
+        get_record(&env, ReceiptKey::Donation(donation_id))
+            .unwrap_or_else(|| panic!("Donation not found"))
+    }
+    
+    /// List up to `limit` donation receipts, starting at receipt id `start`
+    pub fn list_donations(env: Env, start: u64, limit: u32) -> Vec<Donation> {
+        let count: u64 = env.storage().instance().get(&DataKey::Receipt(ReceiptKey::DonationCount)).unwrap_or(0);
+        let mut donations = Vec::new(&env);
+        let mut id = if start == 0 { 1 } else { start };
+        while id <= count && donations.len() < limit {
+            donations.push_back(get_record(&env, ReceiptKey::Donation(id)).unwrap());
+            id += 1;
+        }
+        donations
+    }
+    
+    /// Get the receipt ids of a donor's donations
+    pub fn get_donor_donations(env: Env, donor: Address) -> Vec<u64> {
+        get_record(&env, ReceiptKey::DonorDonations(donor)).unwrap_or(Vec::new(&env))
+    }
+    
+    /// List up to `limit` donors in order of their first donation, starting at donor number `start`
+    pub fn get_donors(env: Env, start: u32, limit: u32) -> Vec<Address> {
+        let count: u32 = env.storage().instance().get(&DataKey::Receipt(ReceiptKey::DonorCount)).unwrap_or(0);
+        let mut donors = Vec::new(&env);
+        let mut number = if start == 0 { 1 } else { start };
+        while number <= count && donors.len() < limit {
+            donors.push_back(get_record(&env, ReceiptKey::Donor(number)).unwrap());
+            number += 1;
+        }
+        donors
+    }
+    
+    /// Get the number of addresses that have donated to the DAO
+    pub fn get_donor_count(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::Receipt(ReceiptKey::DonorCount)).unwrap_or(0)
+    }
+    
+    /// Register a provider (pharmacy, ambulance service, ...) as an allowed payee (admin only)
+    pub fn register_provider(env: Env, admin: Address, provider: Address) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can register providers");
+        }
+        
+        env.storage().instance().set(&DataKey::Provider(provider), &true);
+    }
+    
+    /// Remove a provider from the payee allowlist (admin only)
+    pub fn remove_provider(env: Env, admin: Address, provider: Address) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can remove providers");
+        }
+        
+        env.storage().instance().remove(&DataKey::Provider(provider));
+    }
+    
+    /// Check if an address is a registered provider
+    pub fn is_provider(env: Env, address: Address) -> bool {
+        env.storage().instance().get(&DataKey::Provider(address)).unwrap_or(false)
+    }
+    
+    /// Submit a new proposal (only authorized hospital can do this)
+    /// case_file: commitments to the patient and the encrypted case file
+    /// envelopes: the case file key encrypted to each member's registered key
+    /// token: the treasury asset the proposal is paid in
+    /// line_items: payees and amounts; the amount requested is their sum.
+    /// Every payee must be a registered provider or the authorized hospital.
+    pub fn submit_proposal(
+        env: Env,
+        hospital: Address,
+        case_file: CaseFile,
+        category: Symbol,
+        token: Address,
+        line_items: Vec<LineItem>,
+        envelopes: Map<Address, Bytes>,
+    ) -> u64 {
+        validate_line_items(&env, &line_items);
+        create_proposal(&env, hospital, case_file, category, token, None, line_items, envelopes, None)
+    }
+    
+    /// Submit a proposal quoted in a fiat currency (only authorized hospital can do this)
+    /// Line item amounts are in `currency`, using the same decimals as `token`.
+    /// They are converted to `token` through the price oracle when executed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn submit_fiat_proposal(
+        env: Env,
+        hospital: Address,
+        case_file: CaseFile,
+        category: Symbol,
+        token: Address,
+        currency: Symbol,
+        line_items: Vec<LineItem>,
+        envelopes: Map<Address, Bytes>,
+    ) -> u64 {
+        if !env.storage().instance().has(&DataKey::Oracle) {
+            panic!("Price oracle not configured");
+        }
+        
+        validate_line_items(&env, &line_items);
+        create_proposal(&env, hospital, case_file, category, token, Some(currency), line_items, envelopes, None)
+    }
+    
+    /// Submit a proposal paid out in milestones (only authorized hospital can do this)
+    /// Each milestone is released once the hospital submits evidence and it is confirmed.
+    pub fn submit_milestone_proposal(
+        env: Env,
+        hospital: Address,
+        case_file: CaseFile,
+        category: Symbol,
+        token: Address,
+        milestones: Vec<MilestoneRequest>,
+        envelopes: Map<Address, Bytes>,
+    ) -> u64 {
+        if milestones.is_empty() {
+            panic!("At least one milestone is required");
+        }
+        
+        let mut stored = Vec::new(&env);
+        let mut total: i128 = 0;
+        for request in milestones.iter() {
+            if request.amount <= 0 {
+                panic!("Amount must be positive");
+            }
+            total += request.amount;
+            stored.push_back(Milestone {
+                amount: request.amount,
+                description_hash: request.description_hash,
+                evidence_hash: None,
+                confirmations: 0,
+                released: false,
+            });
+        }
+        
+        // The hospital is the only payee of a milestone proposal
+        let line_items = Vec::from_array(&env, [LineItem {
+            payee: hospital.clone(),
+            amount: total,
+            role: PayeeRole::Hospital,
+        }]);
+        let proposal_id = create_proposal(&env, hospital, case_file, category, token, None, line_items, envelopes, None);
+        env.storage().instance().set(&DataKey::Milestones(proposal_id), &stored);
+        
+        proposal_id
+    }
+    
+    /// Vote on a proposal (only DAO members can vote)
+    /// approve: true to vote for, false to vote against
+    /// An approving vote backs the full amount requested
+    pub fn vote(env: Env, voter: Address, proposal_id: u64, approve: bool) {
+        voter.require_auth();
+        record_vote(&env, voter, proposal_id, approve, None);
+    }
+    
+    /// Vote for a proposal while backing only part of the requested amount
+    /// approved_amount: the amount this voter agrees to release (0 < amount <= requested)
+    pub fn vote_with_amount(env: Env, voter: Address, proposal_id: u64, approved_amount: i128) {
+        voter.require_auth();
+        record_vote(&env, voter, proposal_id, true, Some(approved_amount));
+    }
+    
+    /// Finalize voting on a proposal to determine if it's approved or rejected
+    pub fn finalize_proposal(env: Env, proposal_id: u64) {
+        // Get proposal
+        let mut proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        // Only active proposals can be finalized
+        match proposal.status {
+            ProposalStatus::Active => {},
+            _ => panic!("Proposal is not active"),
+        }
+        
+        // Check if voting threshold is met; appeals need the higher appeal threshold
+        let mut voting_threshold: u32 = env.storage().instance().get(&DataKey::VotingThreshold).unwrap();
+        if proposal.appeal_of.is_some() {
+            voting_threshold = voting_threshold.max(appeal_rule(&env).threshold);
+        }
+        let total_votes = proposal.votes_for + proposal.votes_against;
+        
+        if total_votes < MIN_VOTES {
+            panic!("Not enough votes to finalize (minimum 3 required)");
+        }
+        
+        let approval_percentage = (proposal.votes_for * 100) / total_votes;
+        
+        // Determine final status
+        if approval_percentage >= voting_threshold {
+            // Only proposals approved before a dissolution may still be paid
+            require_not_dissolving(&env);
+            
+            // Settle on the median of the amounts backed by approving voters
+            let amounts: Vec<i128> = env.storage().instance()
+                .get(&DataKey::ApprovedAmounts(proposal_id))
+                .unwrap_or(Vec::new(&env));
+            proposal.amount_approved = median(&amounts);
+            proposal.status = ProposalStatus::Approved;
+            
+            // Fiat proposals lock in a reference quote for the slippage check
+            if let Some(currency) = &proposal.fiat_currency {
+                proposal.fiat_quote = fiat_to_token(&env, &proposal.token, currency, proposal.amount_approved);
+            }
+        } else {
+            proposal.status = ProposalStatus::Rejected;
+            proposal.closed_at = env.ledger().timestamp();
+            release_earmark(&env, &proposal.token, &Restriction::Proposal(proposal_id));
+        }
+        
+        env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+    }
+    
+    /// Execute an approved proposal and release the outstanding approved amount
+    pub fn execute_proposal(env: Env, proposal_id: u64) {
+        let proposal = load_executable_proposal(&env, proposal_id);
+        let outstanding = proposal.amount_approved - proposal.amount_disbursed;
+        
+        // Fiat proposals are converted at the current oracle price
+        let token_amount = match &proposal.fiat_currency {
+            Some(currency) => {
+                let token_amount = fiat_to_token(&env, &proposal.token, currency, outstanding);
+                let config: OracleConfig = env.storage().instance().get(&DataKey::Oracle).unwrap();
+                let max_amount = proposal.fiat_quote
+                    + proposal.fiat_quote * config.max_slippage_bps as i128 / 10_000;
+                if token_amount > max_amount {
+                    panic!("Price moved beyond slippage cap");
+                }
+                token_amount
+            }
+            None => outstanding,
+        };
+        
+        // Check if treasury has enough funds, counting funds restricted to this proposal
+        let treasury = available_funds(&env, &proposal);
+        if treasury < token_amount {
+            panic!("Insufficient funds in treasury");
+        }
+        
+        // Milestone proposals move the full amount into escrow instead of paying out
+        if env.storage().instance().has(&DataKey::Milestones(proposal_id)) {
+            let mut proposal = proposal;
+            escrow_for_proposal(&env, &proposal, outstanding);
+            proposal.status = ProposalStatus::Escrowed;
+            env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+            return;
+        }
+        
+        // Streamed proposals vest the amount to the hospital over time
+        if let Some(duration) = env.storage().instance().get::<_, u64>(&DataKey::StreamDuration(proposal_id)) {
+            let mut proposal = proposal;
+            escrow_for_proposal(&env, &proposal, outstanding);
+            let stream = Stream {
+                amount: outstanding,
+                claimed: 0,
+                start: env.ledger().timestamp(),
+                duration,
+                halted: false,
+                halt_votes: 0,
+            };
+            env.storage().instance().set(&DataKey::Stream(proposal_id), &stream);
+            proposal.status = ProposalStatus::Escrowed;
+            env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+            return;
+        }
+        
+        disburse(&env, proposal, outstanding, token_amount);
+    }
+    
+    /// Execute as much of an approved proposal as the treasury can cover.
+    /// The remainder stays outstanding and can be paid by a later execution.
+    pub fn execute_partial(env: Env, proposal_id: u64) -> i128 {
+        let proposal = load_executable_proposal(&env, proposal_id);
+        let outstanding = proposal.amount_approved - proposal.amount_disbursed;
+        
+        let treasury = available_funds(&env, &proposal);
+        if treasury <= 0 {
+            panic!("Treasury is empty");
+        }
+        
+        if env.storage().instance().has(&DataKey::Milestones(proposal_id)) {
+            panic!("Milestone proposals must be fully funded");
+        }
+        if env.storage().instance().has(&DataKey::StreamDuration(proposal_id)) {
+            panic!("Streamed proposals must be fully funded");
+        }
+        if proposal.fiat_currency.is_some() {
+            panic!("Fiat proposals must be fully funded");
+        }
+        
+        let mut payment = if treasury < outstanding { treasury } else { outstanding };
+        if let Some(budget) = budget_remaining(&env, &proposal.token, &proposal.category) {
+            if budget <= 0 {
+                panic!("Category budget exhausted");
+            }
+            if budget < payment {
+                payment = budget;
+            }
+        }
+        disburse(&env, proposal, payment, payment);
+        payment
+    }
+    
+    /// Get the approved amount that has not been paid out yet
+    pub fn get_outstanding_amount(env: Env, proposal_id: u64) -> i128 {
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        match proposal.status {
+            ProposalStatus::Approved | ProposalStatus::PartiallyExecuted => {
+                proposal.amount_approved - proposal.amount_disbursed
+            }
+            _ => 0,
+        }
+    }
+    
+    /// Configure how milestones are confirmed (admin only)
+    /// confirmations_required: member confirmations needed to release a milestone
+    /// reviewer: optional address whose confirmation alone releases a milestone
+    pub fn set_milestone_review(env: Env, admin: Address, confirmations_required: u32, reviewer: Option<Address>) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can configure milestone review");
+        }
+        
+        if confirmations_required == 0 {
+            panic!("At least one confirmation is required");
+        }
+        
+        env.storage().instance().set(&DataKey::MilestoneConfirmations, &confirmations_required);
+        match reviewer {
+            Some(reviewer) => env.storage().instance().set(&DataKey::MilestoneReviewer, &reviewer),
+            None => env.storage().instance().remove(&DataKey::MilestoneReviewer),
+        }
+    }
+    
+    /// Submit evidence that a milestone was completed (submitting hospital only)
+    pub fn submit_milestone_evidence(
+        env: Env,
+        hospital: Address,
+        proposal_id: u64,
+        milestone_index: u32,
+        evidence_hash: BytesN<32>,
+    ) {
+        hospital.require_auth();
+        
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        if hospital != proposal.hospital {
+            panic!("Only the submitting hospital can submit evidence");
+        }
+        
+        let mut milestones = load_escrowed_milestones(&env, &proposal);
+        let mut milestone = milestones.get(milestone_index)
+            .unwrap_or_else(|| panic!("Milestone not found"));
+        
+        if milestone.evidence_hash.is_some() {
+            panic!("Evidence already submitted");
+        }
+        
+        milestone.evidence_hash = Some(evidence_hash);
+        milestones.set(milestone_index, milestone);
+        env.storage().instance().set(&DataKey::Milestones(proposal_id), &milestones);
+    }
+    
+    /// Confirm a completed milestone (DAO members or the milestone reviewer)
+    /// The milestone is released once the reviewer or enough members confirm it.
+    pub fn confirm_milestone(env: Env, confirmer: Address, proposal_id: u64, milestone_index: u32) {
+        confirmer.require_auth();
+        
+        let is_reviewer = env.storage().instance()
+            .get::<_, Address>(&DataKey::MilestoneReviewer)
+            .map(|reviewer| reviewer == confirmer)
+            .unwrap_or(false);
+        let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(confirmer.clone())).unwrap_or(false);
+        if !is_reviewer && !is_member {
+            panic!("Only DAO members or the milestone reviewer can confirm milestones");
+        }
+        
+        let confirmation_key = DataKey::MilestoneConfirmation(proposal_id, milestone_index, confirmer);
+        if env.storage().instance().has(&confirmation_key) {
+            panic!("Already confirmed this milestone");
+        }
+        
+        let mut proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        let mut milestones = load_escrowed_milestones(&env, &proposal);
+        let mut milestone = milestones.get(milestone_index)
+            .unwrap_or_else(|| panic!("Milestone not found"));
+        
+        if milestone.released {
+            panic!("Milestone already released");
+        }
+        if milestone.evidence_hash.is_none() {
+            panic!("No evidence submitted for this milestone");
+        }
+        
+        milestone.confirmations += 1;
+        env.storage().instance().set(&confirmation_key, &true);
+        
+        let required: u32 = env.storage().instance().get(&DataKey::MilestoneConfirmations).unwrap_or(1);
+        if is_reviewer || milestone.confirmations >= required {
+            // Funds already left the treasury when the proposal was escrowed
+            milestone.released = true;
+            pay_line_items(&env, &mut proposal, milestone.amount, milestone.amount);
+            if proposal.amount_disbursed >= proposal.amount_approved {
+                proposal.status = ProposalStatus::Executed;
+                proposal.closed_at = env.ledger().timestamp();
+            }
+            env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+        }
+        
+        milestones.set(milestone_index, milestone);
+        env.storage().instance().set(&DataKey::Milestones(proposal_id), &milestones);
+    }
+    
+    /// Cancel a proposal that has not been fully paid out (admin only)
+    /// Escrowed funds for unreleased milestones return to the pots they were drawn
+    /// from, and funds donated to the proposal are refunded to their donors.
+    pub fn cancel_proposal(env: Env, admin: Address, proposal_id: u64) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can cancel proposals");
+        }
+        
+        let mut proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        match proposal.status {
+            ProposalStatus::Active | ProposalStatus::Approved | ProposalStatus::PartiallyExecuted => {},
+            ProposalStatus::Escrowed if env.storage().instance().has(&DataKey::Stream(proposal_id)) => {
+                // What already vested stays claimable by the hospital
+                let mut stream: Stream = env.storage().instance().get(&DataKey::Stream(proposal_id)).unwrap();
+                halt_stream(&env, proposal_id, &mut stream);
+            }
+            ProposalStatus::Escrowed => {
+                let unreleased = proposal.amount_approved - proposal.amount_disbursed;
+                return_escrow(&env, &proposal, unreleased);
+            }
+            ProposalStatus::Rejected => panic!("Proposal was rejected"),
+            ProposalStatus::Executed => panic!("Proposal already executed"),
+            ProposalStatus::Cancelled => panic!("Proposal was cancelled"),
+        }
+        release_earmark(&env, &proposal.token, &Restriction::Proposal(proposal_id));
+        
+        proposal.status = ProposalStatus::Cancelled;
+        proposal.closed_at = env.ledger().timestamp();
+        env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+    }
+    
+    /// Get the milestones of a milestone proposal
+    pub fn get_milestones(env: Env, proposal_id: u64) -> Vec<Milestone> {
+        env.storage().instance()
+            .get(&DataKey::Milestones(proposal_id))
+            .unwrap_or(Vec::new(&env))
+    }
+    
+    /// Request that an approved amount vests over `duration` seconds instead of
+    /// being paid at once (submitting hospital only, while voting is open)
+    pub fn request_stream(env: Env, hospital: Address, proposal_id: u64, duration: u64) {
+        hospital.require_auth();
+        
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        if hospital != proposal.hospital {
+            panic!("Only the submitting hospital can request a stream");
+        }
+        
+        match proposal.status {
+            ProposalStatus::Active => {},
+            _ => panic!("Proposal is not active"),
+        }
+        
+        if env.storage().instance().has(&DataKey::Milestones(proposal_id)) {
+            panic!("Milestone proposals cannot be streamed");
+        }
+        if proposal.fiat_currency.is_some() {
+            panic!("Fiat proposals cannot be streamed");
+        }
+        
+        if duration == 0 {
+            panic!("Stream duration must be positive");
+        }
+        
+        env.storage().instance().set(&DataKey::StreamDuration(proposal_id), &duration);
+    }
+    
+    /// Release the vested, unclaimed part of a stream to the proposal's payees
+    /// (submitting hospital only)
+    pub fn claim_stream(env: Env, proposal_id: u64) -> i128 {
+        let mut proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        proposal.hospital.require_auth();
+        
+        let mut stream: Stream = env.storage().instance()
+            .get(&DataKey::Stream(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal has no stream"));
+        
+        let claimable = vested_amount(&env, &stream) - stream.claimed;
+        if claimable <= 0 {
+            panic!("Nothing to claim");
+        }
+        
+        stream.claimed += claimable;
+        env.storage().instance().set(&DataKey::Stream(proposal_id), &stream);
+        
+        pay_line_items(&env, &mut proposal, claimable, claimable);
+        if stream.claimed >= stream.amount {
+            if let ProposalStatus::Escrowed = proposal.status {
+                proposal.status = ProposalStatus::Executed;
+                proposal.closed_at = env.ledger().timestamp();
+            }
+        }
+        env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+        
+        claimable
+    }
+    
+    /// Vote to halt a stream (only DAO members can vote)
+    /// Once the voting threshold of all members vote (and at least 3), the
+    /// unvested remainder returns to the treasury.
+    pub fn vote_halt(env: Env, voter: Address, proposal_id: u64) {
+        voter.require_auth();
+        
+        let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(voter.clone())).unwrap_or(false);
+        if !is_member {
+            panic!("Only DAO members can vote");
+        }
+        
+        let mut stream: Stream = env.storage().instance()
+            .get(&DataKey::Stream(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal has no stream"));
+        
+        if stream.halted {
+            panic!("Stream already halted");
+        }
+        
+        if env.storage().instance().has(&DataKey::HaltVote(proposal_id, voter.clone())) {
+            panic!("Already voted to halt this stream");
+        }
+        env.storage().instance().set(&DataKey::HaltVote(proposal_id, voter), &true);
+        
+        stream.halt_votes += 1;
+        let voting_threshold: u32 = env.storage().instance().get(&DataKey::VotingThreshold).unwrap();
+        let members: Vec<Address> = env.storage().instance().get(&DataKey::Members).unwrap_or(Vec::new(&env));
+        if stream.halt_votes >= MIN_VOTES && stream.halt_votes * 100 >= voting_threshold * members.len() {
+            halt_stream(&env, proposal_id, &mut stream);
+        } else {
+            env.storage().instance().set(&DataKey::Stream(proposal_id), &stream);
+        }
+    }
+    
+    /// Get the stream of a streamed proposal
+    pub fn get_stream(env: Env, proposal_id: u64) -> Stream {
+        env.storage().instance()
+            .get(&DataKey::Stream(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal has no stream"))
+    }
+    
+    /// Get the amount of a stream that has vested so far (claimed or not)
+    pub fn get_vested_amount(env: Env, proposal_id: u64) -> i128 {
+        let stream: Stream = env.storage().instance()
+            .get(&DataKey::Stream(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal has no stream"));
+        vested_amount(&env, &stream)
+    }
+    
+    /// Configure the price oracle used to settle fiat proposals (admin only)
+    /// max_price_age: seconds after which an oracle price is rejected as stale
+    /// max_slippage_bps: how much more the payout may cost, in basis points,
+    /// than the quote taken when the proposal was approved
+    pub fn set_price_oracle(env: Env, admin: Address, oracle: Address, max_price_age: u64, max_slippage_bps: u32) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can set the price oracle");
+        }
+        
+        let config = OracleConfig { oracle, max_price_age, max_slippage_bps };
+        env.storage().instance().set(&DataKey::Oracle, &config);
+    }
+    
+    /// Quote how much of `token` a fiat amount currently costs
+    pub fn quote_fiat(env: Env, token: Address, currency: Symbol, fiat_amount: i128) -> i128 {
+        fiat_to_token(&env, &token, &currency, fiat_amount)
+    }
+    
+    /// Configure the yield vault for a treasury token (admin only)
+    /// liquidity_floor: general funds that must always stay liquid in the DAO
+    pub fn set_vault(env: Env, admin: Address, token: Address, vault: Address, liquidity_floor: i128) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can set the vault");
+        }
+        
+        if liquidity_floor < 0 {
+            panic!("Liquidity floor cannot be negative");
+        }
+        require_treasury_token(&env, &token);
+        
+        let mut config = vault_config(&env, &token)
+            .unwrap_or(VaultConfig { vault: vault.clone(), liquidity_floor, principal: 0 });
+        if config.vault != vault && config.principal > 0 {
+            panic!("Withdraw from the current vault first");
+        }
+        config.vault = vault;
+        config.liquidity_floor = liquidity_floor;
+        env.storage().instance().set(&DataKey::Vault(token), &config);
+    }
+    
+    /// Move idle general funds into the yield vault (admin only)
+    pub fn deposit_to_vault(env: Env, admin: Address, token: Address, amount: i128) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can manage vault deposits");
+        }
+        
+        if amount <= 0 {
+            panic!("Amount must be positive");
+        }
+        require_not_dissolving(&env);
+        
+        let mut config = vault_config(&env, &token).unwrap_or_else(|| panic!("No vault configured"));
+        if liquid_balance(&env, &token) - amount < config.liquidity_floor {
+            panic!("Deposit would breach the liquidity floor");
+        }
+        
+        // The vault pulls the tokens, so authorize that transfer on the DAO's behalf
+        let dao = env.current_contract_address();
+        env.authorize_as_current_contract(vec![
+            &env,
+            InvokerContractAuthEntry::Contract(SubContractInvocation {
+                context: ContractContext {
+                    contract: token.clone(),
+                    fn_name: Symbol::new(&env, "transfer"),
+                    args: (dao.clone(), config.vault.clone(), amount).into_val(&env),
+                },
+                sub_invocations: Vec::new(&env),
+            }),
+        ]);
+        YieldVaultClient::new(&env, &config.vault).deposit(&dao, &amount);
+        
+        config.principal += amount;
+        env.storage().instance().set(&DataKey::Vault(token.clone()), &config);
+        env.storage().instance().set(&DataKey::Holdings(token.clone()), &(holdings(&env, &token) - amount));
+    }
+    
+    /// Move general funds back from the yield vault (admin only)
+    pub fn withdraw_from_vault(env: Env, admin: Address, token: Address, amount: i128) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can manage vault deposits");
+        }
+        
+        let config = vault_config(&env, &token).unwrap_or_else(|| panic!("No vault configured"));
+        if amount <= 0 || amount > config.principal {
+            panic!("Invalid vault withdrawal amount");
+        }
+        recall_from_vault(&env, &token, amount);
+    }
+    
+    /// Collect the vault's yield into general funds (anyone can call)
+    pub fn harvest_yield(env: Env, token: Address) -> i128 {
+        if vault_config(&env, &token).is_none() {
+            panic!("No vault configured");
+        }
+        harvest_vault_yield(&env, &token)
+    }
+    
+    /// Get the vault configuration of a token
+    pub fn get_vault(env: Env, token: Address) -> VaultConfig {
+        vault_config(&env, &token).unwrap_or_else(|| panic!("No vault configured"))
+    }
+    
+    /// Get the general funds of a token held by the DAO itself, outside the vault
+    pub fn get_liquid_balance(env: Env, token: Address) -> i128 {
+        liquid_balance(&env, &token)
+    }
+    
+    /// Plan a budget epoch for a token (admin only)
+    /// Each token's epochs run back to back from `start` to `end`; proposals paid
+    /// in that token while an epoch is active are charged to their category's allocation.
+    /// rollover: carry unspent allocations into the next epoch
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_budget_epoch(
+        env: Env,
+        admin: Address,
+        token: Address,
+        start: u64,
+        end: u64,
+        total_budget: i128,
+        allocations: Map<Symbol, i128>,
+        rollover: bool,
+    ) -> u32 {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can plan budgets");
+        }
+        
+        if end <= start {
+            panic!("Epoch must end after it starts");
+        }
+        let count: u32 = env.storage().instance().get(&DataKey::EpochCount(token.clone())).unwrap_or(0);
+        if count > 0 {
+            let previous: BudgetEpoch = env.storage().instance().get(&DataKey::BudgetEpoch(token.clone(), count)).unwrap();
+            if start < previous.end {
+                panic!("Epochs cannot overlap");
+            }
+        }
+        
+        let mut allocated: i128 = 0;
+        for (_, amount) in allocations.iter() {
+            if amount < 0 {
+                panic!("Allocations cannot be negative");
+            }
+            allocated += amount;
+        }
+        if allocated > total_budget {
+            panic!("Allocations exceed the total budget");
+        }
+        
+        let epoch = BudgetEpoch {
+            id: count + 1,
+            token: token.clone(),
+            start,
+            end,
+            total_budget,
+            allocations,
+            carried: Map::new(&env),
+            spent: Map::new(&env),
+            reserve_spent: 0,
+            rollover,
+            settled: count == 0,
+        };
+        env.storage().instance().set(&DataKey::BudgetEpoch(token.clone(), count + 1), &epoch);
+        env.storage().instance().set(&DataKey::EpochCount(token), &(count + 1));
+        
+        count + 1
+    }
+    
+    /// Get a budget epoch of a token, including any allocations rolled over into it
+    pub fn get_budget_epoch(env: Env, token: Address, epoch_id: u32) -> BudgetEpoch {
+        if !env.storage().instance().has(&DataKey::BudgetEpoch(token.clone(), epoch_id)) {
+            panic!("Budget epoch not found");
+        }
+        settled_epoch(&env, &token, epoch_id)
+    }
+    
+    /// Get the id of the token's budget epoch active at the current ledger time
+    pub fn get_current_epoch(env: Env, token: Address) -> Option<u32> {
+        current_epoch_id(&env, &token)
+    }
+    
+    /// Get what a category can still spend in a token in the current epoch
+    /// (None when no epoch is active and spending is not budgeted)
+    pub fn get_budget_remaining(env: Env, token: Address, category: Symbol) -> Option<i128> {
+        budget_remaining(&env, &token, &category)
+    }
+    
+    /// Set how long recipients have to claim their payouts (admin only)
+    pub fn set_claim_period(env: Env, admin: Address, period: u64) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can set the claim period");
+        }
+        
+        if period == 0 {
+            panic!("Claim period must be positive");
+        }
+        
+        env.storage().instance().set(&DataKey::ClaimPeriod, &period);
+    }
+    
+    /// Withdraw everything of one token credited to a recipient by executed proposals
+    pub fn claim(env: Env, recipient: Address, token: Address) -> i128 {
+        recipient.require_auth();
+        
+        let key = DataKey::Claimable(recipient.clone(), token.clone());
+        let balance: ClaimableBalance = env.storage().instance()
+            .get(&key)
+            .unwrap_or_else(|| panic!("Nothing to claim"));
+        
+        if env.ledger().timestamp() > balance.expires_at {
+            panic!("Claim has expired");
+        }
+        
+        env.storage().instance().remove(&key);
+        send_tokens(&env, &token, &recipient, balance.amount);
+        
+        balance.amount
+    }
+    
+    /// Return an expired, unclaimed balance to the treasury (anyone can call)
+    pub fn reclaim_expired(env: Env, recipient: Address, token: Address) -> i128 {
+        let key = DataKey::Claimable(recipient, token.clone());
+        let balance: ClaimableBalance = env.storage().instance()
+            .get(&key)
+            .unwrap_or_else(|| panic!("Nothing to claim"));
+        
+        if env.ledger().timestamp() <= balance.expires_at {
+            panic!("Claim has not expired");
+        }
+        
+        env.storage().instance().remove(&key);
+        deposit_to_treasury(&env, &token, balance.amount);
+        
+        balance.amount
+    }
+    
+    /// Get the amount of a token a recipient can claim
+    pub fn get_claimable(env: Env, recipient: Address, token: Address) -> i128 {
+        env.storage().instance()
+            .get::<_, ClaimableBalance>(&DataKey::Claimable(recipient, token))
+            .map(|balance| balance.amount)
+            .unwrap_or(0)
+    }
+    
+    /// Get when a recipient's claimable balance of a token expires
+    pub fn get_claim_expiry(env: Env, recipient: Address, token: Address) -> u64 {
+        env.storage().instance()
+            .get::<_, ClaimableBalance>(&DataKey::Claimable(recipient, token))
+            .map(|balance| balance.expires_at)
+            .unwrap_or(0)
+    }
+    
+    /// Get proposal details by ID
+    pub fn get_proposal(env: Env, proposal_id: u64) -> Proposal {
+        env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"))
+    }
+    
+    /// Check a disclosed patient name and salt against a proposal's commitment
+    pub fn verify_patient(env: Env, proposal_id: u64, patient_name: String, salt: BytesN<32>) -> bool {
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        if proposal.tombstone.is_some() {
+            panic!("Patient data was erased");
+        }
+        
+        let mut preimage = Bytes::from_array(&env, &salt.to_array());
+        preimage.append(&patient_name.to_bytes());
+        env.crypto().sha256(&preimage).to_bytes() == proposal.case_file.patient_commitment
+    }
+    
+    /// Limit what one patient can receive across proposals in a denomination (admin only)
+    /// lifetime_cap / period_cap: maximum funding, 0 for no limit
+    /// period: seconds the period cap looks back over
+    /// reject: reject proposals over a cap instead of flagging them
+    pub fn set_patient_caps(
+        env: Env,
+        admin: Address,
+        denomination: Denomination,
+        lifetime_cap: i128,
+        period_cap: i128,
+        period: u64,
+        reject: bool,
+    ) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can set patient caps");
+        }
+        
+        if lifetime_cap < 0 || period_cap < 0 {
+            panic!("Caps cannot be negative");
+        }
+        
+        let caps = PatientCaps { lifetime_cap, period_cap, period, reject };
+        env.storage().instance().set(&DataKey::PatientCaps(denomination), &caps);
+    }
+    
+    /// Get the funding committed to a patient across proposals in a denomination:
+    /// open requests, approved amounts and payouts of closed proposals
+    pub fn get_patient_funding_total(env: Env, patient_id: BytesN<32>, denomination: Denomination) -> i128 {
+        patient_funding(&env, &patient_id, &denomination, 0)
+    }
+    
+    /// Get the proposals submitted for a patient
+    pub fn get_patient_proposals(env: Env, patient_id: BytesN<32>) -> Vec<u64> {
+        env.storage().instance().get(&DataKey::PatientProposals(patient_id)).unwrap_or(Vec::new(&env))
+    }
+    
+    /// Set how long patient data is kept after a proposal closes (admin only)
+    pub fn set_retention_period(env: Env, admin: Address, period: u64) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can set the retention period");
+        }
+        
+        env.storage().instance().set(&DataKey::RetentionPeriod, &period);
+    }
+    
+    /// Erase the case file, envelopes and attachment URIs of a closed proposal
+    /// once the retention period has passed (submitting hospital or admin)
+    /// The financial record stays; the case file is replaced by a tombstone
+    /// hash so an archived copy can still be matched to the proposal. The
+    /// proposal is unlinked from the patient and no longer counts toward caps.
+    pub fn erase_patient_data(env: Env, caller: Address, proposal_id: u64) -> BytesN<32> {
+        caller.require_auth();
+        
+        let mut proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if caller != proposal.hospital && caller != admin {
+            panic!("Only the hospital or admin can erase patient data");
+        }
+        if proposal.tombstone.is_some() {
+            panic!("Patient data already erased");
+        }
+        
+        match proposal.status {
+            ProposalStatus::Executed | ProposalStatus::Rejected | ProposalStatus::Cancelled => {},
+            _ => panic!("Proposal is not closed"),
+        }
+        let retention: u64 = env.storage().instance().get(&DataKey::RetentionPeriod).unwrap_or(DEFAULT_RETENTION_PERIOD);
+        if env.ledger().timestamp() < proposal.closed_at.saturating_add(retention) {
+            panic!("Retention period has not passed");
+        }
+        
+        let tombstone = env.crypto().sha256(&proposal.case_file.clone().to_xdr(&env)).to_bytes();
+        let patient_key = DataKey::PatientProposals(proposal.case_file.patient_id.clone());
+        let mut patient_proposals: Vec<u64> = env.storage().instance().get(&patient_key).unwrap_or(Vec::new(&env));
+        if let Some(index) = patient_proposals.first_index_of(proposal_id) {
+            patient_proposals.remove(index);
+        }
+        if patient_proposals.is_empty() {
+            env.storage().instance().remove(&patient_key);
+        } else {
+            env.storage().instance().set(&patient_key, &patient_proposals);
+        }
+        
+        proposal.case_file = CaseFile {
+            patient_commitment: BytesN::from_array(&env, &[0; 32]),
+            patient_id: BytesN::from_array(&env, &[0; 32]),
+            content_hash: BytesN::from_array(&env, &[0; 32]),
+            uri: String::from_str(&env, ""),
+        };
+        proposal.tombstone = Some(tombstone.clone());
+        env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+        env.storage().instance().remove(&DataKey::Envelopes(proposal_id));
+        
+        // Document hashes stay for audits; where the documents live does not
+        if let Some(attachments) = env.storage().instance().get::<_, Vec<Attachment>>(&DataKey::Attachments(proposal_id)) {
+            let mut kept = Vec::new(&env);
+            for mut attachment in attachments.iter() {
+                attachment.uri = None;
+                kept.push_back(attachment);
+            }
+            env.storage().instance().set(&DataKey::Attachments(proposal_id), &kept);
+        }
+        
+        tombstone
+    }
+    
+    /// Get the amount paid so far to each line item of a proposal
+    pub fn get_line_item_payouts(env: Env, proposal_id: u64) -> Vec<i128> {
+        let proposal: Proposal = env.storage().instance()
+            .get(&DataKey::Proposal(proposal_id))
+            .unwrap_or_else(|| panic!("Proposal not found"));
+        
+        let mut payouts = Vec::new(&env);
+        for index in 0..proposal.line_items.len() {
+            payouts.push_back(line_item_share(&proposal, index, proposal.amount_disbursed));
+        }
+        payouts
+    }
+    
+    /// Get total number of proposals
+    pub fn get_proposal_count(env: Env) -> u64 {
+        env.storage().instance().get(&DataKey::ProposalCount).unwrap_or(0)
+    }
+    
+    /// Get treasury balance of a token
+    pub fn get_treasury_balance(env: Env, token: Address) -> i128 {
+        treasury_balance(&env, &token)
+    }
+    
+    /// Check if an address is a DAO member
+    pub fn is_member(env: Env, address: Address) -> bool {
+        env.storage().instance().get(&DataKey::DAOMember(address)).unwrap_or(false)
+    }
+    
+    /// Check if a voter has voted on a proposal
+    pub fn has_voted(env: Env, proposal_id: u64, voter: Address) -> bool {
+        env.storage().instance().has(&DataKey::Vote(proposal_id, voter))
+    }
+    
+    /// Get voting threshold
+    pub fn get_voting_threshold(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::VotingThreshold).unwrap_or(0)
+    }
+    
+    /// Get authorized hospital address
+    pub fn get_authorized_hospital(env: Env) -> Address {
+        env.storage().instance()
+            .get(&DataKey::AuthorizedHospital)
+            .unwrap_or_else(|| panic!("Authorized hospital not set"))
+    }
+    
+    /// Update authorized hospital (admin only)
+    pub fn set_authorized_hospital(env: Env, admin: Address, new_hospital: Address) {
+        admin.require_auth();
+        
+        let stored_admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if admin != stored_admin {
+            panic!("Only admin can update authorized hospital");
+        }
+        
+        env.storage().instance().set(&DataKey::AuthorizedHospital, &new_hospital);
+    }
+}
+
+/// Credit donated funds to general or restricted treasury funds
+fn credit_donation(env: &Env, donor: &Address, token: &Address, restriction: &Option<Restriction>, amount: i128) {
+    let key = match restriction {
+        Some(restriction) => {
+            deposit_restricted(env, token, restriction.clone(), amount);
+            DataKey::Fund(FundKey::Earmarks(token.clone(), restriction.clone()))
+        }
+        None => {
+            deposit_to_treasury(env, token, amount);
+            DataKey::Fund(FundKey::GeneralDonations(token.clone()))
+        }
+    };
+    let mut shares: Map<Address, i128> = env.storage().instance().get(&key).unwrap_or(Map::new(env));
+    shares.set(donor.clone(), shares.get(donor.clone()).unwrap_or(0) + amount);
+    env.storage().instance().set(&key, &shares);
+}
+
+/// Refund a restricted sub-balance to the donors who restricted it, in
+/// proportion to what each put in. Returns the amount released.
+fn release_earmark(env: &Env, token: &Address, restriction: &Restriction) -> i128 {
+    let balance = restricted_balance(env, token, restriction);
+    let shares_key = DataKey::Fund(FundKey::Earmarks(token.clone(), restriction.clone()));
+    let shares: Map<Address, i128> = env.storage().instance().get(&shares_key).unwrap_or(Map::new(env));
+    env.storage().instance().remove(&shares_key);
+    env.storage().instance().remove(&DataKey::Fund(FundKey::Restricted(token.clone(), restriction.clone())));
+    
+    let mut restrictions: Vec<Restriction> = env.storage().instance()
+        .get(&DataKey::Fund(FundKey::RestrictedFunds(token.clone())))
+        .unwrap_or(Vec::new(env));
+    if let Some(index) = restrictions.first_index_of(restriction) {
+        restrictions.remove(index);
+        env.storage().instance().set(&DataKey::Fund(FundKey::RestrictedFunds(token.clone())), &restrictions);
+    }
+    
+    refund_donors(env, token, &shares, balance);
+    balance
+}
+
+/// Credit `amount` to donors as non-expiring claims, in proportion to their shares
+fn refund_donors(env: &Env, token: &Address, shares: &Map<Address, i128>, amount: i128) {
+    let mut total: i128 = 0;
+    for (_, share) in shares.iter() {
+        total += share;
+    }
+    if amount == 0 || total == 0 {
+        return;
+    }
+    
+    // Cumulative rounding so the refunds add up to exactly `amount`
+    let mut counted: i128 = 0;
+    for (donor, share) in shares.iter() {
+        let refund = (counted + share) * amount / total - counted * amount / total;
+        counted += share;
+        if refund == 0 {
+            continue;
+        }
+        
+        let key = DataKey::Claimable(donor, token.clone());
+        let mut balance: ClaimableBalance = env.storage().instance()
+            .get(&key)
+            .unwrap_or(ClaimableBalance { amount: 0, expires_at: u64::MAX });
+        balance.amount += refund;
+        balance.expires_at = u64::MAX;
+        env.storage().instance().set(&key, &balance);
+    }
+}
+
+/// Donor shares of a token's general funds (pot None) or a restricted sub-balance
+fn pot_shares(env: &Env, token: &Address, pot: &Option<Restriction>) -> Map<Address, i128> {
+    let key = match pot {
+        Some(restriction) => FundKey::Earmarks(token.clone(), restriction.clone()),
+        None => FundKey::GeneralDonations(token.clone()),
+    };
+    env.storage().instance().get(&DataKey::Fund(key)).unwrap_or(Map::new(env))
+}
+
+/// Storage key of a pot's dissolution payout
+fn distribution_key(token: &Address, pot: &Option<Restriction>) -> DataKey {
+    DataKey::Fund(match pot {
+        Some(restriction) => FundKey::EarmarkDistribution(token.clone(), restriction.clone()),
+        None => FundKey::Distribution(token.clone()),
+    })
+}
+
+/// Storage key of what a donor has claimed of a pot's dissolution payout
+fn claimed_key(token: &Address, pot: &Option<Restriction>, donor: &Address) -> DataKey {
+    DataKey::Fund(match pot {
+        Some(restriction) => FundKey::EarmarkClaimed(token.clone(), restriction.clone(), donor.clone()),
+        None => FundKey::DistributionClaimed(token.clone(), donor.clone()),
+    })
+}
+
+/// Add `amount` to what the donors of a pot can claim on dissolution.
+/// Returns false, recording nothing, when the pot has no donors.
+fn share_out(env: &Env, token: &Address, pot: &Option<Restriction>, amount: i128) -> bool {
+    let key = distribution_key(token, pot);
+    let mut distribution: Distribution = match env.storage().instance().get(&key) {
+        Some(distribution) => distribution,
+        // Donations stop once the DAO dissolves, so shares are summed once
+        None => {
+            let mut shares: i128 = 0;
+            for (_, share) in pot_shares(env, token, pot).iter() {
+                shares += share;
+            }
+            Distribution { amount: 0, shares }
+        }
+    };
+    if distribution.shares == 0 {
+        return false;
+    }
+    
+    distribution.amount += amount;
+    env.storage().instance().set(&key, &distribution);
+    true
+}
+
+/// What a donor can still claim of a pot's dissolution payout, rounded down
+fn distribution_owed(env: &Env, donor: &Address, token: &Address, pot: &Option<Restriction>) -> i128 {
+    let Some(distribution) = env.storage().instance().get::<_, Distribution>(&distribution_key(token, pot)) else {
+        return 0;
+    };
+    let share = pot_shares(env, token, pot).get(donor.clone()).unwrap_or(0);
+    let claimed: i128 = env.storage().instance().get(&claimed_key(token, pot, donor)).unwrap_or(0);
+    let owed = share * distribution.amount / distribution.shares;
+    if owed > claimed { owed - claimed } else { 0 }
+}
+
+/// Add a contribution to a proposal's crowdfunding escrow
+fn escrow_contribution(env: &Env, proposal_id: u64, contributor: Address, amount: i128) {
+    let mut crowdfund: Crowdfund = env.storage().instance()
+        .get(&DataKey::Crowdfund(proposal_id))
+        .unwrap_or(Crowdfund { contributed: 0, balance: 0, refund_pool: 0, refunded: 0, closed: false });
+    crowdfund.contributed += amount;
+    crowdfund.balance += amount;
+    env.storage().instance().set(&DataKey::Crowdfund(proposal_id), &crowdfund);
+    
+    let key = DataKey::Contribution(proposal_id, contributor);
+    let contribution: i128 = env.storage().instance().get(&key).unwrap_or(0);
+    env.storage().instance().set(&key, &(contribution + amount));
+}
+
+/// Draw matching funds for `amount` from every eligible pool in `token`.
+/// Donations are eligible when their restriction equals the pool's; proposal
+/// contributions when the pool's restriction matches the proposal.
+/// Pools without a restriction match everything. Returns (sponsor, matched) pairs.
+fn draw_matches(
+    env: &Env,
+    token: &Address,
+    amount: i128,
+    donation_restriction: Option<&Restriction>,
+    proposal: Option<&Proposal>,
+) -> Vec<(Address, i128)> {
+    let mut matches = Vec::new(env);
+    let active: Vec<u64> = env.storage().instance().get(&DataKey::ActivePools).unwrap_or(Vec::new(env));
+    
+    for pool_id in active.iter() {
+        let mut pool: MatchingPool = env.storage().instance().get(&DataKey::Pool(pool_id)).unwrap();
+        if pool.token != *token || pool.remaining == 0 || env.ledger().timestamp() > pool.expires_at {
+            continue;
+        }
+        
+        let eligible = match env.storage().instance().get::<_, Restriction>(&DataKey::PoolRestriction(pool_id)) {
+            None => true,
+            Some(restriction) => match proposal {
+                Some(proposal) => matching_restrictions(env, proposal).contains(&restriction),
+                None => donation_restriction == Some(&restriction),
+            },
+        };
+        if !eligible {
+            continue;
+        }
+        
+        let wanted = amount * pool.ratio_bps as i128 / 10_000;
+        let matched = if wanted < pool.remaining { wanted } else { pool.remaining };
+        if matched == 0 {
+            continue;
+        }
+        
+        pool.remaining -= matched;
+        env.storage().instance().set(&DataKey::Pool(pool_id), &pool);
+        matches.push_back((pool.sponsor, matched));
+    }
+    
+    matches
+}
+
+/// Store a donation receipt and update the donor's totals
+fn record_donation(env: &Env, donor: Address, token: Address, amount: i128) -> u64 {
+    let mut count: u64 = env.storage().instance().get(&DataKey::Receipt(ReceiptKey::DonationCount)).unwrap_or(0);
+    count += 1;
+    
+    let donation = Donation {
+        id: count,
+        donor: donor.clone(),
+        token: token.clone(),
+        amount,
+        timestamp: env.ledger().timestamp(),
+    };
+    set_record(env, ReceiptKey::Donation(count), &donation);
+    env.storage().instance().set(&DataKey::Receipt(ReceiptKey::DonationCount), &count);
+    
+    let mut receipts: Vec<u64> = get_record(env, ReceiptKey::DonorDonations(donor.clone())).unwrap_or(Vec::new(env));
+    if receipts.is_empty() {
+        let donors: u32 = env.storage().instance().get(&DataKey::Receipt(ReceiptKey::DonorCount)).unwrap_or(0);
+        set_record(env, ReceiptKey::Donor(donors + 1), &donor);
+        env.storage().instance().set(&DataKey::Receipt(ReceiptKey::DonorCount), &(donors + 1));
+    }
+    receipts.push_back(count);
+    set_record(env, ReceiptKey::DonorDonations(donor.clone()), &receipts);
+    
+    let total: i128 = get_record(env, ReceiptKey::DonorTotal(donor.clone(), token.clone())).unwrap_or(0);
+    set_record(env, ReceiptKey::DonorTotal(donor, token), &(total + amount));
+    
+    count
+}
+
+/// Store a donation record in persistent storage and keep it alive
+fn set_record<V: IntoVal<Env, Val>>(env: &Env, key: ReceiptKey, value: &V) {
+    let key = DataKey::Receipt(key);
+    env.storage().persistent().set(&key, value);
+    env.storage().persistent().extend_ttl(&key, RECORD_TTL_THRESHOLD, RECORD_TTL);
+}
+
+/// Read a donation record from persistent storage, extending its TTL if it exists
+fn get_record<V: TryFromVal<Env, Val>>(env: &Env, key: ReceiptKey) -> Option<V> {
+    let key = DataKey::Receipt(key);
+    let value = env.storage().persistent().get(&key);
+    if value.is_some() {
+        env.storage().persistent().extend_ttl(&key, RECORD_TTL_THRESHOLD, RECORD_TTL);
+    }
+    value
+}
+
+/// Panic unless every line item has a positive amount and an allowed payee
+fn validate_line_items(env: &Env, line_items: &Vec<LineItem>) {
+    if line_items.is_empty() {
+        panic!("At least one line item is required");
+    }
+    
+    let authorized_hospital: Address = env.storage().instance()
+        .get(&DataKey::AuthorizedHospital)
+        .unwrap_or_else(|| panic!("Authorized hospital not set"));
+    
+    for item in line_items.iter() {
+        if item.amount <= 0 {
+            panic!("Amount must be positive");
+        }
+        let is_provider: bool = env.storage().instance()
+            .get(&DataKey::Provider(item.payee.clone()))
+            .unwrap_or(false);
+        if item.payee != authorized_hospital && !is_provider {
+            panic!("Payee is not a registered provider");
+        }
+    }
+}
+
+/// Validate the submitting hospital and store a new active proposal
+#[allow(clippy::too_many_arguments)]
+fn create_proposal(
+    env: &Env,
+    hospital: Address,
+    case_file: CaseFile,
+    category: Symbol,
+    token: Address,
+    fiat_currency: Option<Symbol>,
+    line_items: Vec<LineItem>,
+    envelopes: Map<Address, Bytes>,
+    appeal_of: Option<u64>,
+) -> u64 {
+    hospital.require_auth();
+    
+    // Check if hospital is authorized
+    let authorized_hospital: Address = env.storage().instance()
+        .get(&DataKey::AuthorizedHospital)
+        .unwrap_or_else(|| panic!("Authorized hospital not set"));
+    
+    if hospital != authorized_hospital {
+        panic!("Only the authorized hospital can submit proposals");
+    }
+    
+    require_not_dissolving(env);
+    require_treasury_token(env, &token);
+    for (member, _) in envelopes.iter() {
+        require_encryption_key(env, &member);
+    }
+    
+    let mut amount_requested: i128 = 0;
+    for item in line_items.iter() {
+        amount_requested += item.amount;
+    }
+    if amount_requested <= 0 {
+        panic!("Amount must be positive");
+    }
+    let over_patient_cap = exceeds_patient_caps(
+        env,
+        &case_file.patient_id,
+        &denomination_of(&token, &fiat_currency),
+        amount_requested,
+    );
+    
+    // Draw a random voting committee when committee mode is on. Appeals go
+    // to members who neither sat on nor voted in the original decision.
+    let mut committee = Vec::new(env);
+    if let Some(mode) = env.storage().instance().get::<_, CommitteeMode>(&DataKey::Review(ReviewKey::CommitteeMode)) {
+        let exclude = appeal_exclusions(env, appeal_of);
+        committee = draw_committee(env, &hospital, &line_items, &exclude, mode.size);
+        if committee.len() < mode.size {
+            panic!("Not enough eligible members for a committee");
+        }
+    }
+    
+    // Get and increment proposal count
+    let mut proposal_count: u64 = env.storage().instance().get(&DataKey::ProposalCount).unwrap_or(0);
+    proposal_count += 1;
+    
+    // Create new proposal
+    let proposal = Proposal {
+        id: proposal_count,
+        hospital,
+        case_file,
+        category,
+        token,
+        fiat_currency,
+        fiat_quote: 0,
+        line_items,
+        amount_requested,
+        amount_approved: 0,
+        amount_disbursed: 0,
+        votes_for: 0,
+        votes_against: 0,
+        status: ProposalStatus::Active,
+        created_at: env.ledger().timestamp(),
+        closed_at: 0,
+        tombstone: None,
+        over_patient_cap,
+        attestations: 0,
+        committee,
+        committee_deadline: 0,
+        appeal_of,
+        appealed_by: None,
+    };
+    
+    // Store proposal
+    env.storage().instance().set(&DataKey::Proposal(proposal_count), &proposal);
+    env.storage().instance().set(&DataKey::ProposalCount, &proposal_count);
+    env.storage().instance().set(&DataKey::Envelopes(proposal_count), &envelopes);
+    start_review(env, proposal_count, 1);
+    start_committee_vote(env, proposal_count);
+    
+    let patient_key = DataKey::PatientProposals(proposal.case_file.patient_id);
+    let mut patient_proposals: Vec<u64> = env.storage().instance().get(&patient_key).unwrap_or(Vec::new(env));
+    patient_proposals.push_back(proposal_count);
+    env.storage().instance().set(&patient_key, &patient_proposals);
+    
+    proposal_count
+}
+
+/// Denomination of amounts paid in `token`, or quoted in `fiat_currency` if set
+fn denomination_of(token: &Address, fiat_currency: &Option<Symbol>) -> Denomination {
+    match fiat_currency {
+        Some(currency) => Denomination::Fiat(currency.clone()),
+        None => Denomination::Token(token.clone()),
+    }
+}
+
+/// Whether funding `amount` more in a denomination would take a patient over
+/// a cap. Panics instead when caps are configured to reject.
+fn exceeds_patient_caps(env: &Env, patient_id: &BytesN<32>, denomination: &Denomination, amount: i128) -> bool {
+    let Some(caps) = env.storage().instance().get::<_, PatientCaps>(&DataKey::PatientCaps(denomination.clone())) else {
+        return false;
+    };
+    
+    let over_lifetime = caps.lifetime_cap > 0
+        && patient_funding(env, patient_id, denomination, 0) + amount > caps.lifetime_cap;
+    let since = env.ledger().timestamp().saturating_sub(caps.period);
+    let over_period = caps.period_cap > 0
+        && patient_funding(env, patient_id, denomination, since) + amount > caps.period_cap;
+    
+    if (over_lifetime || over_period) && caps.reject {
+        panic!("Patient funding cap exceeded");
+    }
+    over_lifetime || over_period
+}
+
+/// Funding committed to a patient by proposals in a denomination created at or after `since`
+fn patient_funding(env: &Env, patient_id: &BytesN<32>, denomination: &Denomination, since: u64) -> i128 {
+    let proposal_ids: Vec<u64> = env.storage().instance()
+        .get(&DataKey::PatientProposals(patient_id.clone()))
+        .unwrap_or(Vec::new(env));
+    
+    let mut total: i128 = 0;
+    for proposal_id in proposal_ids.iter() {
+        let proposal: Proposal = env.storage().instance().get(&DataKey::Proposal(proposal_id)).unwrap();
+        if proposal.created_at >= since && *denomination == denomination_of(&proposal.token, &proposal.fiat_currency) {
+            total += committed_amount(env, &proposal);
+        }
+    }
+    total
+}
+
+/// What a proposal has paid or may still pay: the request while it is being
+/// voted on, the approved amount once approved, and the payout once closed
+fn committed_amount(env: &Env, proposal: &Proposal) -> i128 {
+    if is_expired(env, proposal) {
+        return proposal.amount_disbursed;
+    }
+    match proposal.status {
+        ProposalStatus::Active => proposal.amount_requested,
+        ProposalStatus::Approved | ProposalStatus::PartiallyExecuted | ProposalStatus::Escrowed => {
+            proposal.amount_approved
+        }
+        ProposalStatus::Executed | ProposalStatus::Cancelled => proposal.amount_disbursed,
+        ProposalStatus::Rejected => 0,
+    }
+}
+
+/// Members who cannot review an appeal of `appeal_of`: those who sat on or
+/// voted in the original decision
+fn appeal_exclusions(env: &Env, appeal_of: Option<u64>) -> Vec<Address> {
+    let Some(original_id) = appeal_of else {
+        return Vec::new(env);
+    };
+    let original: Proposal = env.storage().instance().get(&DataKey::Proposal(original_id)).unwrap();
+    let mut exclude: Vec<Address> = env.storage().instance()
+        .get(&DataKey::Review(ReviewKey::Voters(original_id)))
+        .unwrap_or(Vec::new(env));
+    exclude.append(&original.committee);
+    exclude
+}
+
+/// Randomly pick up to `size` members who have no conflict with the proposal:
+/// not the hospital, a payee, a member who declared a conflict with the
+/// hospital, or anyone in `exclude`
+fn draw_committee(env: &Env, hospital: &Address, line_items: &Vec<LineItem>, exclude: &Vec<Address>, size: u32) -> Vec<Address> {
+    let members: Vec<Address> = env.storage().instance().get(&DataKey::Members).unwrap_or(Vec::new(env));
+    let mut eligible = Vec::new(env);
+    for member in members.iter() {
+        let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(member.clone())).unwrap_or(false);
+        let conflicted = member == *hospital
+            || line_items.iter().any(|item| item.payee == member)
+            || env.storage().instance().has(&DataKey::Review(ReviewKey::Conflict(member.clone(), hospital.clone())));
+        if is_member && !conflicted && !exclude.contains(&member) {
+            eligible.push_back(member);
+        }
+    }
+    
+    env.prng().shuffle(&mut eligible);
+    if eligible.len() > size {
+        eligible = eligible.slice(0..size);
+    }
+    eligible
+}
+
+/// Append an attachment to a proposal and return its version
+fn push_attachment(env: &Env, proposal_id: u64, kind: AttachmentKind, hash: BytesN<32>, uri: Option<String>) -> u32 {
+    let mut attachments: Vec<Attachment> = env.storage().instance()
+        .get(&DataKey::Attachments(proposal_id))
+        .unwrap_or(Vec::new(env));
+    let version = attachments.len() + 1;
+    attachments.push_back(Attachment {
+        version,
+        kind: kind.clone(),
+        hash: hash.clone(),
+        uri,
+        added_at: env.ledger().timestamp(),
+    });
+    env.storage().instance().set(&DataKey::Attachments(proposal_id), &attachments);
+    
+    AttachmentAdded { proposal_id, version, kind, hash }.publish(env);
+    version
+}
+
+/// Configured appeal rule, or the defaults
+fn appeal_rule(env: &Env) -> AppealRule {
+    env.storage().instance().get(&DataKey::Review(ReviewKey::AppealRule)).unwrap_or(AppealRule {
+        window: DEFAULT_APPEAL_WINDOW,
+        threshold: DEFAULT_APPEAL_THRESHOLD,
+    })
+}
+
+/// Replace a proposal's case file and line items. The patient must stay the
+/// same and new payees cannot sit on the committee; attestations were given
+/// for the old case and are dropped.
+fn apply_revision(env: &Env, proposal: &mut Proposal, case_file: CaseFile, line_items: Vec<LineItem>) {
+    if env.storage().instance().has(&DataKey::Milestones(proposal.id)) {
+        panic!("Milestone proposals cannot be revised");
+    }
+    if case_file.patient_id != proposal.case_file.patient_id {
+        panic!("Patient cannot change");
+    }
+    validate_line_items(env, &line_items);
+    if proposal.committee.iter().any(|member| line_items.iter().any(|item| item.payee == member)) {
+        panic!("Payee sits on the proposal's committee");
+    }
+    
+    let mut amount_requested: i128 = 0;
+    for item in line_items.iter() {
+        amount_requested += item.amount;
+    }
+    proposal.over_patient_cap = exceeds_patient_caps(
+        env,
+        &case_file.patient_id,
+        &denomination_of(&proposal.token, &proposal.fiat_currency),
+        amount_requested - proposal.amount_requested,
+    );
+    
+    proposal.case_file = case_file;
+    proposal.line_items = line_items;
+    proposal.amount_requested = amount_requested;
+    proposal.attestations = 0;
+    env.storage().instance().remove(&DataKey::Review(ReviewKey::Attestations(proposal.id)));
+}
+
+/// Discard every vote cast on a proposal
+fn clear_votes(env: &Env, proposal: &mut Proposal) {
+    let voters: Vec<Address> = env.storage().instance()
+        .get(&DataKey::Review(ReviewKey::Voters(proposal.id)))
+        .unwrap_or(Vec::new(env));
+    for voter in voters.iter() {
+        env.storage().instance().remove(&DataKey::Vote(proposal.id, voter.clone()));
+        env.storage().instance().remove(&DataKey::VoteAttachments(proposal.id, voter));
+    }
+    env.storage().instance().remove(&DataKey::Review(ReviewKey::Voters(proposal.id)));
+    env.storage().instance().remove(&DataKey::ApprovedAmounts(proposal.id));
+    proposal.votes_for = 0;
+    proposal.votes_against = 0;
+}
+
+/// Put a proposal into triage when a review pipeline is configured
+fn start_review(env: &Env, proposal_id: u64, round: u32) {
+    let Some(pipeline) = env.storage().instance().get::<_, ReviewPipeline>(&DataKey::Review(ReviewKey::Pipeline)) else {
+        return;
+    };
+    let review = Review {
+        stage: ReviewStage::Triage,
+        round,
+        deadline: env.ledger().timestamp() + pipeline.triage_period,
+        approved_by: Vec::new(env),
+    };
+    env.storage().instance().set(&DataKey::Review(ReviewKey::Review(proposal_id)), &review);
+}
+
+/// Whether members can vote on a proposal: it has passed any review
+/// pipeline and collected the attestations the attestation rule requires
+fn is_voting_open(env: &Env, proposal: &Proposal) -> bool {
+    if let Some(review) = env.storage().instance().get::<_, Review>(&DataKey::Review(ReviewKey::Review(proposal.id))) {
+        if review.stage != ReviewStage::Voting || env.ledger().timestamp() > review.deadline {
+            return false;
+        }
+    }
+    if let Some(rule) = env.storage().instance().get::<_, AttestationRule>(&DataKey::Review(ReviewKey::AttestationRule)) {
+        if counted_attestations(env, proposal.id, &rule) < rule.required {
+            return false;
+        }
+    }
+    true
+}
+
+/// Start a committee's vote period once voting opens, so members are only
+/// counted absent after they could vote; cleared while voting is closed
+fn start_committee_vote(env: &Env, proposal_id: u64) {
+    let mut proposal: Proposal = env.storage().instance().get(&DataKey::Proposal(proposal_id)).unwrap();
+    if proposal.committee.is_empty() {
+        return;
+    }
+    
+    proposal.committee_deadline = 0;
+    if is_voting_open(env, &proposal) {
+        let mode: Option<CommitteeMode> = env.storage().instance().get(&DataKey::Review(ReviewKey::CommitteeMode));
+        let vote_period = mode.map(|mode| mode.vote_period).unwrap_or(0);
+        proposal.committee_deadline = env.ledger().timestamp() + vote_period;
+    }
+    env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+}
+
+/// Load an active proposal's review, checking it is in `stage` and within its deadline
+fn load_review_stage(env: &Env, proposal_id: u64, stage: ReviewStage) -> Review {
+    let proposal: Proposal = env.storage().instance()
+        .get(&DataKey::Proposal(proposal_id))
+        .unwrap_or_else(|| panic!("Proposal not found"));
+    match proposal.status {
+        ProposalStatus::Active => {},
+        _ => panic!("Proposal is not active"),
+    }
+    
+    let review: Review = env.storage().instance()
+        .get(&DataKey::Review(ReviewKey::Review(proposal_id)))
+        .unwrap_or_else(|| panic!("Proposal is not under review"));
+    if review.stage != stage {
+        panic!("Proposal is not at this review stage");
+    }
+    if env.ledger().timestamp() > review.deadline {
+        panic!("Stage deadline has passed");
+    }
+    review
+}
+
+/// Panic unless `account` holds a review role
+fn require_review_role(env: &Env, account: &Address, role: ReviewRole) {
+    if !env.storage().instance().has(&DataKey::Review(ReviewKey::Role(account.clone(), role))) {
+        panic!("Missing review role");
+    }
+}
+
+/// Attestations of a proposal that count toward the attestation rule
+fn counted_attestations(env: &Env, proposal_id: u64, rule: &AttestationRule) -> u32 {
+    let attestations: Vec<Attestation> = env.storage().instance()
+        .get(&DataKey::Review(ReviewKey::Attestations(proposal_id)))
+        .unwrap_or(Vec::new(env));
+    if !rule.independent_only {
+        return attestations.len();
+    }
+    attestations.iter().filter(|attestation| attestation.independent).count() as u32
+}
+
+/// Panic unless `member` is a DAO member with a registered encryption key
+fn require_encryption_key(env: &Env, member: &Address) {
+    let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(member.clone())).unwrap_or(false);
+    if !is_member {
+        panic!("Envelope recipient is not a member");
+    }
+    if !env.storage().instance().has(&DataKey::EncryptionKey(member.clone())) {
+        panic!("Member has no encryption key");
+    }
+}
+
+/// Record a vote, optionally with the amount an approving voter backs
+fn record_vote(env: &Env, voter: Address, proposal_id: u64, approve: bool, approved_amount: Option<i128>) {
+    // Check if voter is a DAO member
+    let is_member: bool = env.storage().instance().get(&DataKey::DAOMember(voter.clone())).unwrap_or(false);
+    if !is_member {
+        panic!("Only DAO members can vote");
+    }
+    
+    // Check if already voted
+    if env.storage().instance().has(&DataKey::Vote(proposal_id, voter.clone())) {
+        panic!("Already voted on this proposal");
+    }
+    
+    // Get proposal
+    let mut proposal: Proposal = env.storage().instance()
+        .get(&DataKey::Proposal(proposal_id))
+        .unwrap_or_else(|| panic!("Proposal not found"));
+    
+    // Check if proposal is still active (not executed or permanently closed)
+    match proposal.status {
+        ProposalStatus::Active | ProposalStatus::Approved | ProposalStatus::Rejected => {},
+        ProposalStatus::PartiallyExecuted | ProposalStatus::Escrowed | ProposalStatus::Executed => {
+            panic!("Proposal already executed")
+        }
+        ProposalStatus::Cancelled => panic!("Proposal was cancelled"),
+    }
+    
+    if !proposal.committee.is_empty() && !proposal.committee.contains(&voter) {
+        panic!("Only committee members can vote on this proposal");
+    }
+    
+    // Proposals under review can only be voted on once they reach the vote
+    if let Some(review) = env.storage().instance().get::<_, Review>(&DataKey::Review(ReviewKey::Review(proposal_id))) {
+        if review.stage != ReviewStage::Voting {
+            panic!("Proposal is not open for voting");
+        }
+        if env.ledger().timestamp() > review.deadline {
+            panic!("Voting period has ended");
+        }
+    }
+    
+    // Voting opens once enough doctors have attested medical necessity
+    if let Some(rule) = env.storage().instance().get::<_, AttestationRule>(&DataKey::Review(ReviewKey::AttestationRule)) {
+        if counted_attestations(env, proposal_id, &rule) < rule.required {
+            panic!("Medical necessity not attested");
+        }
+    }
+    
+    // Milestone proposals are all-or-nothing: the milestones define the amount
+    if approved_amount.is_some() && env.storage().instance().has(&DataKey::Milestones(proposal_id)) {
+        panic!("Milestone proposals cannot be approved for a different amount");
+    }
+    
+    // Record vote
+    if approve {
+        let amount = approved_amount.unwrap_or(proposal.amount_requested);
+        if amount <= 0 || amount > proposal.amount_requested {
+            panic!("Approved amount must be between 1 and the amount requested");
+        }
+        
+        // Keep the backed amounts sorted so the median is cheap to read on finalize
+        let mut amounts: Vec<i128> = env.storage().instance()
+            .get(&DataKey::ApprovedAmounts(proposal_id))
+            .unwrap_or(Vec::new(env));
+        let mut index = amounts.len();
+        for (i, existing) in amounts.iter().enumerate() {
+            if amount < existing {
+                index = i as u32;
+                break;
+            }
+        }
+        amounts.insert(index, amount);
+        env.storage().instance().set(&DataKey::ApprovedAmounts(proposal_id), &amounts);
+        
+        proposal.votes_for += 1;
+    } else {
+        proposal.votes_against += 1;
+    }
+    
+    // Remember which documents the voter could see
+    let attachments: Vec<Attachment> = env.storage().instance()
+        .get(&DataKey::Attachments(proposal_id))
+        .unwrap_or(Vec::new(env));
+    env.storage().instance().set(&DataKey::VoteAttachments(proposal_id, voter.clone()), &attachments.len());
+    
+    let mut voters: Vec<Address> = env.storage().instance()
+        .get(&DataKey::Review(ReviewKey::Voters(proposal_id)))
+        .unwrap_or(Vec::new(env));
+    voters.push_back(voter.clone());
+    env.storage().instance().set(&DataKey::Review(ReviewKey::Voters(proposal_id)), &voters);
+    
+    // Store vote and updated proposal
+    env.storage().instance().set(&DataKey::Vote(proposal_id, voter), &approve);
+    env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+}
+
+/// Median of a sorted list; for an even count the lower middle value is used
+/// so that at least half of the approving voters backed the released amount
+fn median(sorted: &Vec<i128>) -> i128 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    sorted.get((sorted.len() - 1) / 2).unwrap()
+}
+
+/// Load a proposal that is approved and still has an outstanding amount to pay
+fn load_executable_proposal(env: &Env, proposal_id: u64) -> Proposal {
+    let proposal: Proposal = env.storage().instance()
+        .get(&DataKey::Proposal(proposal_id))
+        .unwrap_or_else(|| panic!("Proposal not found"));
+    
+    // Check if proposal is approved
+    match proposal.status {
+        ProposalStatus::Approved | ProposalStatus::PartiallyExecuted => {},
+        ProposalStatus::Escrowed | ProposalStatus::Executed => panic!("Proposal already executed"),
+        ProposalStatus::Rejected => panic!("Proposal was rejected"),
+        ProposalStatus::Cancelled => panic!("Proposal was cancelled"),
+        ProposalStatus::Active => panic!("Proposal is still active, not enough votes"),
+    }
+    
+    if is_expired(env, &proposal) {
+        panic!("Proposal has expired");
+    }
+    
+    proposal
+}
+
+/// Whether an open proposal has outlived the configured proposal lifetime
+/// or the grace period of an approved dissolution
+fn is_expired(env: &Env, proposal: &Proposal) -> bool {
+    match proposal.status {
+        ProposalStatus::Active | ProposalStatus::Approved | ProposalStatus::PartiallyExecuted => {},
+        _ => return false,
+    }
+    
+    if let Some(grace_ends) = dissolution_grace_end(env) {
+        if env.ledger().timestamp() > grace_ends {
+            return true;
+        }
+    }
+    
+    let lifetime: u64 = env.storage().instance().get(&DataKey::ProposalLifetime).unwrap_or(0);
+    lifetime != 0 && env.ledger().timestamp() > proposal.created_at + lifetime
+}
+
+/// End of the grace period, if the DAO is being dissolved
+fn dissolution_grace_end(env: &Env) -> Option<u64> {
+    let dissolution: Dissolution = env.storage().instance().get(&DataKey::Dissolution)?;
+    match dissolution.status {
+        DissolutionStatus::Approved => Some(dissolution.grace_ends),
+        _ => None,
+    }
+}
+
+/// Panic once members have approved dissolving the DAO
+fn require_not_dissolving(env: &Env) {
+    if dissolution_grace_end(env).is_some() {
+        panic!("DAO is dissolving");
+    }
+}
+
+/// Whether a proposal will not draw on its crowdfunding escrow any more
+fn is_funding_closed(env: &Env, proposal: &Proposal) -> bool {
+    match proposal.status {
+        ProposalStatus::Rejected | ProposalStatus::Cancelled
+        | ProposalStatus::Escrowed | ProposalStatus::Executed => true,
+        _ => is_expired(env, proposal),
+    }
+}
+
+/// Whether a closed proposal can no longer return escrowed funds, so the
+/// crowdfund's refund pool is final
+fn is_funding_settled(env: &Env, proposal: &Proposal) -> bool {
+    match proposal.status {
+        ProposalStatus::Escrowed => env.storage().instance()
+            .get::<_, Stream>(&DataKey::Stream(proposal.id))
+            .is_some_and(|stream| stream.halted),
+        _ => is_funding_closed(env, proposal),
+    }
+}
+
+/// Load the dissolution motion that is open for voting
+fn load_dissolution_motion(env: &Env) -> Dissolution {
+    let dissolution: Dissolution = env.storage().instance()
+        .get(&DataKey::Dissolution)
+        .unwrap_or_else(|| panic!("No dissolution proposed"));
+    match dissolution.status {
+        DissolutionStatus::Voting => dissolution,
+        _ => panic!("Dissolution vote is closed"),
+    }
+}
+
+/// Load the milestones of a proposal whose funds are held in escrow
+fn load_escrowed_milestones(env: &Env, proposal: &Proposal) -> Vec<Milestone> {
+    let milestones: Vec<Milestone> = env.storage().instance()
+        .get(&DataKey::Milestones(proposal.id))
+        .unwrap_or_else(|| panic!("Proposal has no milestones"));
+    
+    match proposal.status {
+        ProposalStatus::Escrowed => {},
+        _ => panic!("Proposal funds are not in escrow"),
+    }
+    
+    milestones
+}
+
+/// Amount of a stream vested at the current ledger time
+fn vested_amount(env: &Env, stream: &Stream) -> i128 {
+    if stream.halted {
+        return stream.amount;
+    }
+    
+    let elapsed = env.ledger().timestamp().saturating_sub(stream.start);
+    if elapsed >= stream.duration {
+        return stream.amount;
+    }
+    stream.amount * elapsed as i128 / stream.duration as i128
+}
+
+/// Stop a stream, returning the unvested remainder to the pots it was drawn from
+fn halt_stream(env: &Env, proposal_id: u64, stream: &mut Stream) {
+    let mut proposal: Proposal = env.storage().instance().get(&DataKey::Proposal(proposal_id)).unwrap();
+    let vested = vested_amount(env, stream);
+    return_escrow(env, &proposal, stream.amount - vested);
+    
+    stream.amount = vested;
+    stream.halted = true;
+    env.storage().instance().set(&DataKey::Stream(proposal_id), stream);
+    
+    // Nothing left to claim means the proposal is fully paid
+    if stream.claimed >= stream.amount {
+        if let ProposalStatus::Escrowed = proposal.status {
+            proposal.status = ProposalStatus::Executed;
+            proposal.closed_at = env.ledger().timestamp();
+            env.storage().instance().set(&DataKey::Proposal(proposal_id), &proposal);
+        }
+    }
+}
+
+/// Release `amount` of a proposal's approved funds from the treasury
+/// `token_amount` is what `amount` costs in the proposal's token.
+fn disburse(env: &Env, mut proposal: Proposal, amount: i128, token_amount: i128) {
+    withdraw_for_proposal(env, &proposal, token_amount);
+    pay_line_items(env, &mut proposal, amount, token_amount);
+    
+    // Mark proposal as executed once the approved amount is fully paid
+    if proposal.amount_disbursed >= proposal.amount_approved {
+        proposal.status = ProposalStatus::Executed;
+        proposal.closed_at = env.ledger().timestamp();
+    } else {
+        proposal.status = ProposalStatus::PartiallyExecuted;
+    }
+    env.storage().instance().set(&DataKey::Proposal(proposal.id), &proposal);
+}
+
+/// Panic unless the token is a whitelisted treasury asset
+fn require_treasury_token(env: &Env, token: &Address) {
+    if !env.storage().instance().has(&DataKey::TreasuryToken(token.clone())) {
+        panic!("Token is not a treasury asset");
+    }
+}
+
+/// Convert a fiat amount to `token` using the configured price oracle.
+/// Both prices are read in the oracle's base asset, so their decimals cancel out.
+fn fiat_to_token(env: &Env, token: &Address, currency: &Symbol, fiat_amount: i128) -> i128 {
+    let config: OracleConfig = env.storage().instance()
+        .get(&DataKey::Oracle)
+        .unwrap_or_else(|| panic!("Price oracle not configured"));
+    let oracle = PriceOracleClient::new(env, &config.oracle);
+    
+    let token_price = fresh_price(env, &config, oracle.lastprice(&Asset::Stellar(token.clone())));
+    let fiat_price = fresh_price(env, &config, oracle.lastprice(&Asset::Other(currency.clone())));
+    
+    fiat_amount * fiat_price / token_price
+}
+
+/// Panic unless the oracle returned a positive price within the staleness bound
+fn fresh_price(env: &Env, config: &OracleConfig, price: Option<PriceData>) -> i128 {
+    let price = price.unwrap_or_else(|| panic!("Oracle has no price"));
+    if env.ledger().timestamp() > price.timestamp + config.max_price_age {
+        panic!("Oracle price is stale");
+    }
+    if price.price <= 0 {
+        panic!("Oracle price must be positive");
+    }
+    price.price
+}
+
+/// Transfer tokens from `from` into the DAO contract
+fn receive_tokens(env: &Env, token: &Address, from: &Address, amount: i128) {
+    token::Client::new(env, token).transfer(from, env.current_contract_address(), &amount);
+    env.storage().instance().set(&DataKey::Holdings(token.clone()), &(holdings(env, token) + amount));
+}
+
+/// Transfer tokens held by the DAO contract to `to`
+fn send_tokens(env: &Env, token: &Address, to: &Address, amount: i128) {
+    token::Client::new(env, token).transfer(&env.current_contract_address(), to, &amount);
+    env.storage().instance().set(&DataKey::Holdings(token.clone()), &(holdings(env, token) - amount));
+}
+
+/// Tokens the DAO should hold according to its books
+fn holdings(env: &Env, token: &Address) -> i128 {
+    env.storage().instance().get(&DataKey::Holdings(token.clone())).unwrap_or(0)
+}
+
+/// Treasury balance of a token
+fn treasury_balance(env: &Env, token: &Address) -> i128 {
+    env.storage().instance().get(&DataKey::Treasury(token.clone())).unwrap_or(0)
+}
+
+/// Add funds to the treasury balance of a token
+fn deposit_to_treasury(env: &Env, token: &Address, amount: i128) {
+    let treasury = treasury_balance(env, token) + amount;
+    env.storage().instance().set(&DataKey::Treasury(token.clone()), &treasury);
+}
+
+/// Deduct funds from the treasury balance of a token
+fn withdraw_from_treasury(env: &Env, token: &Address, amount: i128) {
+    let treasury = treasury_balance(env, token) - amount;
+    env.storage().instance().set(&DataKey::Treasury(token.clone()), &treasury);
+}
+
+/// Id of the token's budget epoch active at the current ledger time
+fn current_epoch_id(env: &Env, token: &Address) -> Option<u32> {
+    let count: u32 = env.storage().instance().get(&DataKey::EpochCount(token.clone())).unwrap_or(0);
+    let now = env.ledger().timestamp();
+    for id in (1..=count).rev() {
+        let epoch: BudgetEpoch = env.storage().instance().get(&DataKey::BudgetEpoch(token.clone(), id)).unwrap();
+        if epoch.start <= now {
+            return if now < epoch.end { Some(id) } else { None };
+        }
+    }
+    None
+}
+
+/// Load an epoch with the previous epoch's unspent allocations rolled over.
+/// Only meaningful once the previous epoch has ended.
+fn settled_epoch(env: &Env, token: &Address, epoch_id: u32) -> BudgetEpoch {
+    let mut epoch: BudgetEpoch = env.storage().instance().get(&DataKey::BudgetEpoch(token.clone(), epoch_id)).unwrap();
+    if epoch.settled {
+        return epoch;
+    }
+    
+    let previous = settled_epoch(env, token, epoch_id - 1);
+    if previous.rollover {
+        let mut categories = previous.allocations.keys();
+        categories.append(&previous.carried.keys());
+        for category in categories.iter() {
+            if epoch.carried.contains_key(category.clone()) {
+                continue;
+            }
+            let unspent = category_remaining(&previous, &category);
+            if unspent > 0 {
+                epoch.carried.set(category, unspent);
+            }
+        }
+    }
+    epoch.settled = true;
+    epoch
+}
+
+/// What a category with an allocation or carry-over can still spend in an epoch
+fn category_remaining(epoch: &BudgetEpoch, category: &Symbol) -> i128 {
+    epoch.allocations.get(category.clone()).unwrap_or(0)
+        + epoch.carried.get(category.clone()).unwrap_or(0)
+        - epoch.spent.get(category.clone()).unwrap_or(0)
+}
+
+/// What a category can still spend in an epoch; categories without an
+/// allocation draw on the unallocated part of the total budget
+fn epoch_remaining(epoch: &BudgetEpoch, category: &Symbol) -> i128 {
+    if epoch.allocations.contains_key(category.clone()) || epoch.carried.contains_key(category.clone()) {
+        return category_remaining(epoch, category);
+    }
+    let mut allocated: i128 = 0;
+    for (_, amount) in epoch.allocations.iter() {
+        allocated += amount;
+    }
+    epoch.total_budget - allocated - epoch.reserve_spent
+}
+
+/// Remaining budget of a category in the token's current epoch, if one is active
+fn budget_remaining(env: &Env, token: &Address, category: &Symbol) -> Option<i128> {
+    let epoch_id = current_epoch_id(env, token)?;
+    Some(epoch_remaining(&settled_epoch(env, token, epoch_id), category))
+}
+
+/// Charge spending in a token on a category to the token's current epoch, if one is active
+fn charge_budget(env: &Env, token: &Address, category: &Symbol, amount: i128) -> Option<u32> {
+    let epoch_id = current_epoch_id(env, token)?;
+    let mut epoch = settled_epoch(env, token, epoch_id);
+    if amount > epoch_remaining(&epoch, category) {
+        panic!("Category budget exceeded");
+    }
+    
+    if epoch.allocations.contains_key(category.clone()) || epoch.carried.contains_key(category.clone()) {
+        let spent = epoch.spent.get(category.clone()).unwrap_or(0);
+        epoch.spent.set(category.clone(), spent + amount);
+    } else {
+        epoch.reserve_spent += amount;
+    }
+    env.storage().instance().set(&DataKey::BudgetEpoch(token.clone(), epoch_id), &epoch);
+    Some(epoch_id)
+}
+
+/// Give back spending charged to an epoch for funds that were not paid out
+fn refund_budget(env: &Env, token: &Address, epoch_id: u32, category: &Symbol, amount: i128) {
+    let mut epoch = settled_epoch(env, token, epoch_id);
+    if epoch.allocations.contains_key(category.clone()) || epoch.carried.contains_key(category.clone()) {
+        let spent = epoch.spent.get(category.clone()).unwrap_or(0);
+        epoch.spent.set(category.clone(), spent - amount);
+    } else {
+        epoch.reserve_spent -= amount;
+    }
+    env.storage().instance().set(&DataKey::BudgetEpoch(token.clone(), epoch_id), &epoch);
+}
+
+/// Vault configuration of a token, if any
+fn vault_config(env: &Env, token: &Address) -> Option<VaultConfig> {
+    env.storage().instance().get(&DataKey::Vault(token.clone()))
+}
+
+/// General funds of a token not deposited in its vault
+fn liquid_balance(env: &Env, token: &Address) -> i128 {
+    let invested = vault_config(env, token).map(|config| config.principal).unwrap_or(0);
+    treasury_balance(env, token) - invested
+}
+
+/// Withdraw part of the vault principal back into the DAO
+fn recall_from_vault(env: &Env, token: &Address, amount: i128) {
+    if amount == 0 {
+        return;
+    }
+    let mut config = vault_config(env, token).unwrap();
+    YieldVaultClient::new(env, &config.vault).withdraw(&env.current_contract_address(), &amount);
+    
+    config.principal -= amount;
+    env.storage().instance().set(&DataKey::Vault(token.clone()), &config);
+    env.storage().instance().set(&DataKey::Holdings(token.clone()), &(holdings(env, token) + amount));
+}
+
+/// Withdraw whatever the vault holds above the principal into general funds
+fn harvest_vault_yield(env: &Env, token: &Address) -> i128 {
+    let config = vault_config(env, token).unwrap();
+    let vault = YieldVaultClient::new(env, &config.vault);
+    let earned = vault.balance(&env.current_contract_address()) - config.principal;
+    if earned <= 0 {
+        return 0;
+    }
+    
+    vault.withdraw(&env.current_contract_address(), &earned);
+    env.storage().instance().set(&DataKey::Holdings(token.clone()), &(holdings(env, token) + earned));
+    deposit_to_treasury(env, token, earned);
+    earned
+}
+
+/// Restricted sub-balance of a token
+fn restricted_balance(env: &Env, token: &Address, restriction: &Restriction) -> i128 {
+    env.storage().instance()
+        .get(&DataKey::Fund(FundKey::Restricted(token.clone(), restriction.clone())))
+        .unwrap_or(0)
+}
+
+/// Add funds to a restricted sub-balance of a token
+fn deposit_restricted(env: &Env, token: &Address, restriction: Restriction, amount: i128) {
+    let balance = restricted_balance(env, token, &restriction);
+    if balance == 0 {
+        let mut restrictions: Vec<Restriction> = env.storage().instance()
+            .get(&DataKey::Fund(FundKey::RestrictedFunds(token.clone())))
+            .unwrap_or(Vec::new(env));
+        if !restrictions.contains(&restriction) {
+            restrictions.push_back(restriction.clone());
+            env.storage().instance().set(&DataKey::Fund(FundKey::RestrictedFunds(token.clone())), &restrictions);
+        }
+    }
+    env.storage().instance().set(&DataKey::Fund(FundKey::Restricted(token.clone(), restriction)), &(balance + amount));
+}
+
+/// Restrictions a proposal's payouts can draw from, most specific first
+fn matching_restrictions(env: &Env, proposal: &Proposal) -> Vec<Restriction> {
+    Vec::from_array(env, [
+        Restriction::Proposal(proposal.id),
+        Restriction::Hospital(proposal.hospital.clone()),
+        Restriction::Category(proposal.category.clone()),
+    ])
+}
+
+/// Funds a proposal can be paid from: its crowdfunding escrow, matching
+/// restricted funds and general funds
+fn available_funds(env: &Env, proposal: &Proposal) -> i128 {
+    let mut available = treasury_balance(env, &proposal.token);
+    if let Some(crowdfund) = env.storage().instance().get::<_, Crowdfund>(&DataKey::Crowdfund(proposal.id)) {
+        available += crowdfund.balance;
+    }
+    for restriction in matching_restrictions(env, proposal).iter() {
+        available += restricted_balance(env, &proposal.token, &restriction);
+    }
+    available
+}
+
+/// Withdraw funds for a proposal, drawing from its crowdfunding escrow, then
+/// matching restricted funds and finally general funds. Returns where they came from.
+fn withdraw_for_proposal(env: &Env, proposal: &Proposal, amount: i128) -> Funding {
+    let epoch = charge_budget(env, &proposal.token, &proposal.category, amount);
+    let mut draws = Vec::new(env);
+    
+    let mut remaining = amount;
+    if let Some(mut crowdfund) = env.storage().instance().get::<_, Crowdfund>(&DataKey::Crowdfund(proposal.id)) {
+        let drawn = if crowdfund.balance < remaining { crowdfund.balance } else { remaining };
+        crowdfund.balance -= drawn;
+        remaining -= drawn;
+        env.storage().instance().set(&DataKey::Crowdfund(proposal.id), &crowdfund);
+        if drawn > 0 {
+            draws.push_back(FundDraw { source: FundSource::Crowdfund, amount: drawn });
+        }
+    }
+    
+    for restriction in matching_restrictions(env, proposal).iter() {
+        if remaining == 0 {
+            break;
+        }
+        let balance = restricted_balance(env, &proposal.token, &restriction);
+        let drawn = if balance < remaining { balance } else { remaining };
+        if drawn > 0 {
+            env.storage().instance().set(&DataKey::Fund(FundKey::Restricted(proposal.token.clone(), restriction.clone())), &(balance - drawn));
+            remaining -= drawn;
+            draws.push_back(FundDraw { source: FundSource::Restricted(restriction), amount: drawn });
+        }
+    }
+    
+    // Top up from the vault when liquid general funds fall short, restoring
+    // the liquidity floor as well where the vault allows
+    if let Some(config) = vault_config(env, &proposal.token) {
+        let liquid = liquid_balance(env, &proposal.token);
+        if remaining > liquid {
+            let wanted = remaining - liquid + config.liquidity_floor;
+            let recalled = if wanted < config.principal { wanted } else { config.principal };
+            recall_from_vault(env, &proposal.token, recalled);
+        }
+    }
+    withdraw_from_treasury(env, &proposal.token, remaining);
+    if remaining > 0 {
+        draws.push_back(FundDraw { source: FundSource::General, amount: remaining });
+    }
+    Funding { epoch, draws }
+}
+
+/// Withdraw a proposal's funds into escrow, remembering where they came from
+fn escrow_for_proposal(env: &Env, proposal: &Proposal, amount: i128) {
+    let funding = withdraw_for_proposal(env, proposal, amount);
+    env.storage().instance().set(&DataKey::Fund(FundKey::EscrowFunding(proposal.id)), &funding);
+}
+
+/// Return `amount` of a proposal's unreleased escrow to the pots it was drawn
+/// from and give it back to the budget. Released payouts are taken to have
+/// used the pots in the order they were drawn, so the last pots are refilled first.
+fn return_escrow(env: &Env, proposal: &Proposal, amount: i128) {
+    let Some(funding) = env.storage().instance().get::<_, Funding>(&DataKey::Fund(FundKey::EscrowFunding(proposal.id))) else {
+        deposit_to_treasury(env, &proposal.token, amount);
+        return;
+    };
+    if let Some(epoch_id) = funding.epoch {
+        refund_budget(env, &proposal.token, epoch_id, &proposal.category, amount);
+    }
+    
+    let mut remaining = amount;
+    for draw in funding.draws.iter().rev() {
+        if remaining == 0 {
+            break;
+        }
+        let returned = if draw.amount < remaining { draw.amount } else { remaining };
+        remaining -= returned;
+        match draw.source {
+            FundSource::Crowdfund => {
+                let mut crowdfund: Crowdfund = env.storage().instance().get(&DataKey::Crowdfund(proposal.id)).unwrap();
+                crowdfund.balance += returned;
+                env.storage().instance().set(&DataKey::Crowdfund(proposal.id), &crowdfund);
+            }
+            FundSource::Restricted(restriction) => deposit_restricted(env, &proposal.token, restriction, returned),
+            FundSource::General => deposit_to_treasury(env, &proposal.token, returned),
+        }
+    }
+}
+
+/// Pay `amount` of a proposal across its line items by crediting each payee's
+/// claimable balance. Payees withdraw with `claim`, so one recipient that cannot
+/// receive tokens does not block the execution for everyone else.
+/// `token_amount` is the token value of `amount` (they differ for fiat proposals).
+fn pay_line_items(env: &Env, proposal: &mut Proposal, amount: i128, token_amount: i128) {
+    let before = proposal.amount_disbursed;
+    proposal.amount_disbursed += amount;
+    
+    let claim_period: u64 = env.storage().instance().get(&DataKey::ClaimPeriod).unwrap_or(DEFAULT_CLAIM_PERIOD);
+    let expires_at = env.ledger().timestamp() + claim_period;
+    
+    let last = proposal.line_items.len() - 1;
+    let mut credited: i128 = 0;
+    for index in 0..proposal.line_items.len() {
+        let payment = line_item_share(proposal, index, proposal.amount_disbursed)
+            - line_item_share(proposal, index, before);
+        
+        // Convert to tokens; the last item takes the rounding remainder
+        let token_payment = if index == last {
+            token_amount - credited
+        } else {
+            payment * token_amount / amount
+        };
+        if token_payment == 0 {
+            continue;
+        }
+        credited += token_payment;
+        
+        let payee = proposal.line_items.get(index).unwrap().payee;
+        let key = DataKey::Claimable(payee, proposal.token.clone());
+        let mut balance: ClaimableBalance = env.storage().instance()
+            .get(&key)
+            .unwrap_or(ClaimableBalance { amount: 0, expires_at });
+        balance.amount += token_payment;
+        // Never shorten an existing claim, e.g. a refund that does not expire
+        balance.expires_at = balance.expires_at.max(expires_at);
+        env.storage().instance().set(&key, &balance);
+    }
+}
+
+/// Cumulative amount owed to a line item once `disbursed` has been paid in total.
+/// Items share each payment pro rata to their requested amounts; the last item
+/// takes the rounding remainder so the shares always add up to `disbursed`.
+fn line_item_share(proposal: &Proposal, index: u32, disbursed: i128) -> i128 {
+    let last = proposal.line_items.len() - 1;
+    if index < last {
+        let item = proposal.line_items.get(index).unwrap();
+        return item.amount * disbursed / proposal.amount_requested;
+    }
+    
+    let mut others: i128 = 0;
+    for i in 0..last {
+        others += line_item_share(proposal, i, disbursed);
     }
+    disbursed - others
 }
 
 mod test;
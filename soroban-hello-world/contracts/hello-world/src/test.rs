@@ -1,7 +1,113 @@
 #![cfg(test)]
 
 use super::*;
-use soroban_sdk::{testutils::Address as _, Address, Env, String};
+use soroban_sdk::xdr::ToXdr;
+use soroban_sdk::{
+    testutils::{storage::Persistent as _, Address as _, Events, Ledger, MockAuth, MockAuthInvoke},
+    token::{StellarAssetClient, TokenClient},
+    contract, contractimpl, symbol_short, vec, Address, Bytes, BytesN, Env, IntoVal, Map, String, Symbol, Vec,
+};
+
+fn create_treasury_token(env: &Env, client: &EmergencyFundDAOClient, admin: &Address) -> Address {
+    let token = env.register_stellar_asset_contract_v2(admin.clone()).address();
+    client.add_treasury_token(admin, &token);
+    token
+}
+
+fn donate(env: &Env, client: &EmergencyFundDAOClient, token: &Address, amount: i128, restriction: Option<Restriction>) {
+    let donor = Address::generate(env);
+    StellarAssetClient::new(env, token).mint(&donor, &amount);
+    client.add_funds(&donor, token, &amount, &restriction);
+}
+
+fn single_payee(env: &Env, hospital: &Address, amount: i128) -> Vec<LineItem> {
+    vec![env, LineItem { payee: hospital.clone(), amount, role: PayeeRole::Hospital }]
+}
+
+fn patient_salt(env: &Env) -> BytesN<32> {
+    BytesN::from_array(env, &[7; 32])
+}
+
+/// Patient id commitment, using the name as a stand-in for the national id
+fn patient_id(env: &Env, national_id: &str) -> BytesN<32> {
+    let mut preimage = Bytes::from_slice(env, b"dao-pepper");
+    preimage.append(&Bytes::from_slice(env, national_id.as_bytes()));
+    env.crypto().sha256(&preimage).to_bytes()
+}
+
+/// Case file committing to a patient name with `patient_salt`
+fn case_file(env: &Env, patient_name: &str, details: &str) -> CaseFile {
+    let mut preimage = Bytes::from_array(env, &patient_salt(env).to_array());
+    preimage.append(&Bytes::from_slice(env, patient_name.as_bytes()));
+    CaseFile {
+        patient_commitment: env.crypto().sha256(&preimage).to_bytes(),
+        patient_id: patient_id(env, patient_name),
+        content_hash: env.crypto().sha256(&Bytes::from_slice(env, details.as_bytes())).to_bytes(),
+        uri: String::from_str(env, "ipfs://encrypted-case-file"),
+    }
+}
+
+/// DAO with a 66% voting threshold, one treasury token and three members
+struct TestDao<'a> {
+    env: &'a Env,
+    client: EmergencyFundDAOClient<'a>,
+    admin: Address,
+    hospital: Address,
+    token: Address,
+    members: Vec<Address>,
+}
+
+impl<'a> TestDao<'a> {
+    fn new(env: &'a Env) -> Self {
+        let contract_id = env.register(EmergencyFundDAO, ());
+        let client = EmergencyFundDAOClient::new(env, &contract_id);
+
+        let admin = Address::generate(env);
+        let hospital = Address::generate(env);
+        let members = vec![env, Address::generate(env), Address::generate(env), Address::generate(env)];
+        
+        client.initialize(&admin, &66, &hospital);
+        let token = create_treasury_token(env, &client, &admin);
+        for member in members.iter() {
+            client.add_member(&admin, &member);
+        }
+        
+        TestDao { env, client, admin, hospital, token, members }
+    }
+    
+    /// Add members until there are `count` of them
+    fn with_members(mut self, count: u32) -> Self {
+        while self.members.len() < count {
+            let member = Address::generate(self.env);
+            self.client.add_member(&self.admin, &member);
+            self.members.push_back(member);
+        }
+        self
+    }
+    
+    /// Grow to six members and draw committees of three from a seeded PRNG
+    fn with_committees(self, seed: u8) -> Self {
+        self.env.host().set_base_prng_seed([seed; 32]).unwrap();
+        let dao = self.with_members(6);
+        dao.client.set_committee_mode(&dao.admin, &3, &100);
+        dao
+    }
+    
+    /// Donate `amount` of unrestricted funds
+    fn funded(self, amount: i128) -> Self {
+        donate(self.env, &self.client, &self.token, amount, None);
+        self
+    }
+}
+
+/// Have every member vote the same way on a proposal and finalize it
+fn voted_proposal(client: &EmergencyFundDAOClient, members: &Vec<Address>, proposal_id: u64, approve: bool) -> u64 {
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &approve);
+    }
+    client.finalize_proposal(&proposal_id);
+    proposal_id
+}
 
 #[test]
 fn test_initialize() {
@@ -13,10 +119,12 @@ fn test_initialize() {
     
     let hospital = Address::generate(&env);
     client.initialize(&admin, &66, &hospital);
+    let token = env.register_stellar_asset_contract_v2(admin.clone()).address();
     
     assert_eq!(client.get_voting_threshold(), 66);
     assert_eq!(client.get_proposal_count(), 0);
-    assert_eq!(client.get_treasury_balance(), 0);
+    assert_eq!(client.get_treasury_balance(&token), 0);
+    assert_eq!(client.get_treasury_tokens().len(), 0);
 }
 
 #[test]
@@ -55,19 +163,43 @@ fn test_add_member() {
 #[test]
 fn test_add_funds() {
     let env = Env::default();
+    env.mock_all_auths();
+    
     let contract_id = env.register(EmergencyFundDAO, ());
     let client = EmergencyFundDAOClient::new(&env, &contract_id);
 
     let admin = Address::generate(&env);
+    let donor = Address::generate(&env);
     
     let hospital = Address::generate(&env);
     client.initialize(&admin, &66, &hospital);
-    client.add_funds(&1000);
+    let token = create_treasury_token(&env, &client, &admin);
+    StellarAssetClient::new(&env, &token).mint(&donor, &2000);
+    
+    client.add_funds(&donor, &token, &1000, &None);
+    assert_eq!(client.get_treasury_balance(&token), 1000);
     
-    assert_eq!(client.get_treasury_balance(), 1000);
+    client.add_funds(&donor, &token, &500, &None);
+    assert_eq!(client.get_treasury_balance(&token), 1500);
     
-    client.add_funds(&500);
-    assert_eq!(client.get_treasury_balance(), 1500);
+    // Tokens actually moved from the donor to the DAO
+    let token_client = TokenClient::new(&env, &token);
+    assert_eq!(token_client.balance(&donor), 500);
+    assert_eq!(token_client.balance(&contract_id), 1500);
+}
+
+#[test]
+#[should_panic(expected = "Token is not a treasury asset")]
+fn test_add_funds_unlisted_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, .. } = TestDao::new(&env);
+    let donor = Address::generate(&env);
+    let token = env.register_stellar_asset_contract_v2(admin.clone()).address();
+    StellarAssetClient::new(&env, &token).mint(&donor, &1000);
+    
+    client.add_funds(&donor, &token, &1000, &None); // Should panic
 }
 
 #[test]
@@ -79,16 +211,18 @@ fn test_submit_proposal() {
     let client = EmergencyFundDAOClient::new(&env, &contract_id);
 
     let admin = Address::generate(&env);
-    let hospital = Address::generate(&env);
     
     let hospital = Address::generate(&env);
     client.initialize(&admin, &66, &hospital);
+    let token = create_treasury_token(&env, &client, &admin);
     
     let proposal_id = client.submit_proposal(
         &hospital,
-        &String::from_str(&env, "John Doe"),
-        &String::from_str(&env, "Emergency heart surgery required"),
-        &5000,
+        &case_file(&env, "John Doe", "Emergency heart surgery required"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 5000),
+        &Map::new(&env),
     );
     
     assert_eq!(proposal_id, 1);
@@ -110,7 +244,6 @@ fn test_voting_and_approval() {
     let client = EmergencyFundDAOClient::new(&env, &contract_id);
 
     let admin = Address::generate(&env);
-    let hospital = Address::generate(&env);
     let member1 = Address::generate(&env);
     let member2 = Address::generate(&env);
     let member3 = Address::generate(&env);
@@ -118,6 +251,7 @@ fn test_voting_and_approval() {
     // Initialize DAO with 66% threshold
     let hospital = Address::generate(&env);
     client.initialize(&admin, &66, &hospital);
+    let token = create_treasury_token(&env, &client, &admin);
     
     // Add members
     client.add_member(&admin, &member1);
@@ -127,9 +261,11 @@ fn test_voting_and_approval() {
     // Submit proposal
     let proposal_id = client.submit_proposal(
         &hospital,
-        &String::from_str(&env, "Jane Smith"),
-        &String::from_str(&env, "Cancer treatment needed"),
-        &10000,
+        &case_file(&env, "Jane Smith", "Cancer treatment needed"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 10000),
+        &Map::new(&env),
     );
     
     // Members vote - 2 for, 1 against (66% approval)
@@ -160,22 +296,24 @@ fn test_voting_and_rejection() {
     let client = EmergencyFundDAOClient::new(&env, &contract_id);
 
     let admin = Address::generate(&env);
-    let hospital = Address::generate(&env);
     let member1 = Address::generate(&env);
     let member2 = Address::generate(&env);
     let member3 = Address::generate(&env);
     
     let hospital = Address::generate(&env);
     client.initialize(&admin, &66, &hospital);
+    let token = create_treasury_token(&env, &client, &admin);
     client.add_member(&admin, &member1);
     client.add_member(&admin, &member2);
     client.add_member(&admin, &member3);
     
     let proposal_id = client.submit_proposal(
         &hospital,
-        &String::from_str(&env, "Test Patient"),
-        &String::from_str(&env, "Test case"),
-        &5000,
+        &case_file(&env, "Test Patient", "Test case"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 5000),
+        &Map::new(&env),
     );
     
     // 1 for, 2 against (33% approval, less than 66% threshold)
@@ -204,18 +342,20 @@ fn test_double_voting() {
     let client = EmergencyFundDAOClient::new(&env, &contract_id);
 
     let admin = Address::generate(&env);
-    let hospital = Address::generate(&env);
     let member = Address::generate(&env);
     
     let hospital = Address::generate(&env);
     client.initialize(&admin, &66, &hospital);
+    let token = create_treasury_token(&env, &client, &admin);
     client.add_member(&admin, &member);
     
     let proposal_id = client.submit_proposal(
         &hospital,
-        &String::from_str(&env, "Test"),
-        &String::from_str(&env, "Test"),
-        &1000,
+        &case_file(&env, "Test", "Test"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::new(&env),
     );
     
     client.vote(&member, &proposal_id, &true);
@@ -231,14 +371,14 @@ fn test_execute_proposal() {
     let client = EmergencyFundDAOClient::new(&env, &contract_id);
 
     let admin = Address::generate(&env);
-    let hospital = Address::generate(&env);
     let member1 = Address::generate(&env);
     let member2 = Address::generate(&env);
     let member3 = Address::generate(&env);
     
     let hospital = Address::generate(&env);
     client.initialize(&admin, &66, &hospital);
-    client.add_funds(&20000);
+    let token = create_treasury_token(&env, &client, &admin);
+    donate(&env, &client, &token, 20000, None);
     
     client.add_member(&admin, &member1);
     client.add_member(&admin, &member2);
@@ -246,9 +386,11 @@ fn test_execute_proposal() {
     
     let proposal_id = client.submit_proposal(
         &hospital,
-        &String::from_str(&env, "Emergency Patient"),
-        &String::from_str(&env, "Urgent surgery"),
-        &10000,
+        &case_file(&env, "Emergency Patient", "Urgent surgery"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 10000),
+        &Map::new(&env),
     );
     
     // Vote to approve
@@ -263,7 +405,7 @@ fn test_execute_proposal() {
     client.execute_proposal(&proposal_id);
     
     // Check treasury was deducted
-    assert_eq!(client.get_treasury_balance(), 10000);
+    assert_eq!(client.get_treasury_balance(&token), 10000);
     
     // Check proposal is marked as executed
     let proposal = client.get_proposal(&proposal_id);
@@ -283,14 +425,14 @@ fn test_execute_without_funds() {
     let client = EmergencyFundDAOClient::new(&env, &contract_id);
 
     let admin = Address::generate(&env);
-    let hospital = Address::generate(&env);
     let member1 = Address::generate(&env);
     let member2 = Address::generate(&env);
     let member3 = Address::generate(&env);
     
     let hospital = Address::generate(&env);
     client.initialize(&admin, &66, &hospital);
-    client.add_funds(&1000); // Not enough funds
+    let token = create_treasury_token(&env, &client, &admin);
+    donate(&env, &client, &token, 1000, None); // Not enough funds
     
     client.add_member(&admin, &member1);
     client.add_member(&admin, &member2);
@@ -298,9 +440,11 @@ fn test_execute_without_funds() {
     
     let proposal_id = client.submit_proposal(
         &hospital,
-        &String::from_str(&env, "Patient"),
-        &String::from_str(&env, "Treatment"),
-        &5000,
+        &case_file(&env, "Patient", "Treatment"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 5000),
+        &Map::new(&env),
     );
     
     client.vote(&member1, &proposal_id, &true);
@@ -331,7 +475,8 @@ fn test_complete_workflow() {
     
     // Initialize DAO with authorized hospital
     client.initialize(&admin, &75, &authorized_hospital); // 75% threshold
-    client.add_funds(&50000);
+    let token = create_treasury_token(&env, &client, &admin);
+    donate(&env, &client, &token, 50000, None);
     
     // Add members
     client.add_member(&admin, &member1);
@@ -342,17 +487,21 @@ fn test_complete_workflow() {
     // Authorized hospital submits proposal 1
     let proposal1 = client.submit_proposal(
         &authorized_hospital,
-        &String::from_str(&env, "Alice Johnson"),
-        &String::from_str(&env, "Heart transplant"),
-        &15000,
+        &case_file(&env, "Alice Johnson", "Heart transplant"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &authorized_hospital, 15000),
+        &Map::new(&env),
     );
     
     // Authorized hospital submits proposal 2
     let proposal2 = client.submit_proposal(
         &authorized_hospital,
-        &String::from_str(&env, "Bob Williams"),
-        &String::from_str(&env, "Cancer treatment"),
-        &12000,
+        &case_file(&env, "Bob Williams", "Cancer treatment"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &authorized_hospital, 12000),
+        &Map::new(&env),
     );
     
     assert_eq!(client.get_proposal_count(), 2);
@@ -378,7 +527,7 @@ fn test_complete_workflow() {
     // Execute approved proposal
     client.execute_proposal(&proposal1);
     
-    assert_eq!(client.get_treasury_balance(), 35000); // 50000 - 15000
+    assert_eq!(client.get_treasury_balance(&token), 35000); // 50000 - 15000
     
     let p1 = client.get_proposal(&proposal1);
     let p2 = client.get_proposal(&proposal2);
@@ -404,7 +553,6 @@ fn test_mock_data_output() {
 
     // Setup mock addresses
     let admin = Address::generate(&env);
-    let hospital = Address::generate(&env);
     let member1 = Address::generate(&env);
     let member2 = Address::generate(&env);
     let member3 = Address::generate(&env);
@@ -412,12 +560,13 @@ fn test_mock_data_output() {
     // Initialize DAO with 66% threshold
     let hospital = Address::generate(&env);
     client.initialize(&admin, &66, &hospital);
+    let token = create_treasury_token(&env, &client, &admin);
     assert_eq!(client.get_voting_threshold(), 66);
-    assert_eq!(client.get_treasury_balance(), 0);
+    assert_eq!(client.get_treasury_balance(&token), 0);
     
     // Add funds to treasury
-    client.add_funds(&100000);
-    assert_eq!(client.get_treasury_balance(), 100000);
+    donate(&env, &client, &token, 100000, None);
+    assert_eq!(client.get_treasury_balance(&token), 100000);
     
     // Add members to DAO
     client.add_member(&admin, &member1);
@@ -430,9 +579,11 @@ fn test_mock_data_output() {
     // Hospital submits emergency proposal
     let proposal_id = client.submit_proposal(
         &hospital,
-        &String::from_str(&env, "Sarah Martinez"),
-        &String::from_str(&env, "Emergency: Acute heart failure requiring immediate bypass surgery. Patient is 45-year-old with no insurance."),
-        &25000,
+        &case_file(&env, "Sarah Martinez", "Emergency: Acute heart failure requiring immediate bypass surgery. Patient is 45-year-old with no insurance."),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 25000),
+        &Map::new(&env),
     );
     
     assert_eq!(proposal_id, 1);
@@ -440,7 +591,7 @@ fn test_mock_data_output() {
     
     let proposal = client.get_proposal(&proposal_id);
     assert_eq!(proposal.id, 1);
-    assert_eq!(proposal.patient_name, String::from_str(&env, "Sarah Martinez"));
+    assert!(client.verify_patient(&proposal_id, &String::from_str(&env, "Sarah Martinez"), &patient_salt(&env)));
     assert_eq!(proposal.amount_requested, 25000);
     assert_eq!(proposal.votes_for, 0);
     assert_eq!(proposal.votes_against, 0);
@@ -477,12 +628,12 @@ fn test_mock_data_output() {
     }
     
     // Execute proposal and release funds
-    let treasury_before = client.get_treasury_balance();
+    let treasury_before = client.get_treasury_balance(&token);
     assert_eq!(treasury_before, 100000);
     
     client.execute_proposal(&proposal_id);
     
-    let treasury_after = client.get_treasury_balance();
+    let treasury_after = client.get_treasury_balance(&token);
     assert_eq!(treasury_after, 75000); // 100000 - 25000
     
     let executed = client.get_proposal(&proposal_id);
@@ -493,6 +644,2467 @@ fn test_mock_data_output() {
     
     // Verify final state
     assert_eq!(client.get_proposal_count(), 1);
-    assert_eq!(client.get_treasury_balance(), 75000);
+    assert_eq!(client.get_treasury_balance(&token), 75000);
     assert_eq!(client.get_voting_threshold(), 66);
 }
+
+#[test]
+fn test_amended_amounts_settle_on_median() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env).with_members(4).funded(20000);
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Treatment"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 10000),
+        &Map::new(&env),
+    );
+    
+    // Approving amounts: 10000 (full), 6000, 8000 -> median 8000
+    client.vote(&members.get(0).unwrap(), &proposal_id, &true);
+    client.vote_with_amount(&members.get(1).unwrap(), &proposal_id, &6000);
+    client.vote_with_amount(&members.get(2).unwrap(), &proposal_id, &8000);
+    client.vote(&members.get(3).unwrap(), &proposal_id, &false);
+    
+    client.finalize_proposal(&proposal_id);
+    
+    let proposal = client.get_proposal(&proposal_id);
+    assert_eq!(proposal.amount_requested, 10000);
+    assert_eq!(proposal.amount_approved, 8000);
+    assert_eq!(client.get_outstanding_amount(&proposal_id), 8000);
+    
+    client.execute_proposal(&proposal_id);
+    
+    let executed = client.get_proposal(&proposal_id);
+    assert_eq!(executed.amount_disbursed, 8000);
+    assert_eq!(client.get_treasury_balance(&token), 12000);
+    assert_eq!(client.get_outstanding_amount(&proposal_id), 0);
+}
+
+#[test]
+#[should_panic(expected = "Approved amount must be between 1 and the amount requested")]
+fn test_vote_with_amount_above_request() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Treatment"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::new(&env),
+    );
+    
+    client.vote_with_amount(&members.get(0).unwrap(), &proposal_id, &1500); // Should panic
+}
+
+#[test]
+fn test_partial_execution() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    // Only part of the request is covered
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env).funded(3000);
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Treatment"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 5000),
+        &Map::new(&env),
+    );
+    voted_proposal(&client, &members, proposal_id, true);
+    
+    // First execution pays what the treasury holds
+    assert_eq!(client.execute_partial(&proposal_id), 3000);
+    assert_eq!(client.get_treasury_balance(&token), 0);
+    assert_eq!(client.get_outstanding_amount(&proposal_id), 2000);
+    
+    let partial = client.get_proposal(&proposal_id);
+    assert_eq!(partial.amount_disbursed, 3000);
+    match partial.status {
+        ProposalStatus::PartiallyExecuted => {},
+        _ => panic!("Proposal should be partially executed"),
+    }
+    
+    // Remainder is paid once the treasury is topped up
+    donate(&env, &client, &token, 4000, None);
+    client.execute_proposal(&proposal_id);
+    assert_eq!(client.get_treasury_balance(&token), 2000);
+    assert_eq!(client.get_outstanding_amount(&proposal_id), 0);
+    
+    match client.get_proposal(&proposal_id).status {
+        ProposalStatus::Executed => {},
+        _ => panic!("Proposal should be executed"),
+    }
+}
+
+/// Chemotherapy course in three cycles
+fn submit_milestones(env: &Env, client: &EmergencyFundDAOClient, token: &Address) -> u64 {
+    let hospital = client.get_authorized_hospital();
+    client.submit_milestone_proposal(
+        &hospital,
+        &case_file(env, "Patient", "Chemotherapy course"),
+        &symbol_short!("trauma"),
+        token,
+        &vec![
+            env,
+            MilestoneRequest { amount: 4000, description_hash: BytesN::from_array(env, &[1; 32]) },
+            MilestoneRequest { amount: 3000, description_hash: BytesN::from_array(env, &[2; 32]) },
+            MilestoneRequest { amount: 3000, description_hash: BytesN::from_array(env, &[3; 32]) },
+        ],
+        &Map::new(env),
+    )
+}
+
+#[test]
+fn test_milestone_release_by_members() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env).funded(20000);
+    let proposal_id = voted_proposal(&client, &members, submit_milestones(&env, &client, &token), true);
+    let confirmer1 = Address::generate(&env);
+    let confirmer2 = Address::generate(&env);
+    client.add_member(&admin, &confirmer1);
+    client.add_member(&admin, &confirmer2);
+    client.set_milestone_review(&admin, &2, &None);
+    
+    assert_eq!(client.get_proposal(&proposal_id).amount_requested, 10000);
+    
+    // Execution moves the whole amount into escrow
+    client.execute_proposal(&proposal_id);
+    assert_eq!(client.get_treasury_balance(&token), 10000);
+    match client.get_proposal(&proposal_id).status {
+        ProposalStatus::Escrowed => {},
+        _ => panic!("Proposal should be escrowed"),
+    }
+    
+    client.submit_milestone_evidence(&hospital, &proposal_id, &0, &BytesN::from_array(&env, &[9; 32]));
+    
+    // One confirmation is not enough
+    client.confirm_milestone(&confirmer1, &proposal_id, &0);
+    assert!(!client.get_milestones(&proposal_id).get(0).unwrap().released);
+    assert_eq!(client.get_proposal(&proposal_id).amount_disbursed, 0);
+    
+    client.confirm_milestone(&confirmer2, &proposal_id, &0);
+    let milestone = client.get_milestones(&proposal_id).get(0).unwrap();
+    assert!(milestone.released);
+    assert_eq!(milestone.confirmations, 2);
+    assert_eq!(client.get_proposal(&proposal_id).amount_disbursed, 4000);
+}
+
+#[test]
+fn test_milestone_reviewer_and_cancellation() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env).funded(20000);
+    let proposal_id = voted_proposal(&client, &members, submit_milestones(&env, &client, &token), true);
+    let reviewer = Address::generate(&env);
+    client.set_milestone_review(&admin, &3, &Some(reviewer.clone()));
+    
+    client.execute_proposal(&proposal_id);
+    
+    // The reviewer alone releases a milestone
+    client.submit_milestone_evidence(&hospital, &proposal_id, &0, &BytesN::from_array(&env, &[9; 32]));
+    client.confirm_milestone(&reviewer, &proposal_id, &0);
+    assert_eq!(client.get_proposal(&proposal_id).amount_disbursed, 4000);
+    
+    // Cancelling returns the unreleased milestones to the treasury
+    client.cancel_proposal(&admin, &proposal_id);
+    assert_eq!(client.get_treasury_balance(&token), 16000);
+    
+    match client.get_proposal(&proposal_id).status {
+        ProposalStatus::Cancelled => {},
+        _ => panic!("Proposal should be cancelled"),
+    }
+}
+
+#[test]
+#[should_panic(expected = "No evidence submitted for this milestone")]
+fn test_confirm_milestone_without_evidence() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env).funded(20000);
+    let proposal_id = voted_proposal(&client, &members, submit_milestones(&env, &client, &token), true);
+    let confirmer = Address::generate(&env);
+    client.add_member(&admin, &confirmer);
+    
+    client.execute_proposal(&proposal_id);
+    client.confirm_milestone(&confirmer, &proposal_id, &1); // Should panic
+}
+
+/// Dialysis for 100 days, streamed to the hospital
+fn submit_stream(env: &Env, client: &EmergencyFundDAOClient, token: &Address) -> u64 {
+    let hospital = client.get_authorized_hospital();
+    env.ledger().set_timestamp(1000);
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(env, "Patient", "Dialysis for 100 days"),
+        &symbol_short!("trauma"),
+        token,
+        &single_payee(env, &hospital, 10000),
+        &Map::new(env),
+    );
+    client.request_stream(&hospital, &proposal_id, &100);
+    proposal_id
+}
+
+#[test]
+fn test_stream_vesting_and_claims() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env).funded(20000);
+    let proposal_id = voted_proposal(&client, &members, submit_stream(&env, &client, &token), true);
+    
+    env.ledger().set_timestamp(2000);
+    client.execute_proposal(&proposal_id);
+    assert_eq!(client.get_treasury_balance(&token), 10000);
+    assert_eq!(client.get_vested_amount(&proposal_id), 0);
+    
+    // A quarter of the way through
+    env.ledger().set_timestamp(2025);
+    assert_eq!(client.get_vested_amount(&proposal_id), 2500);
+    assert_eq!(client.claim_stream(&proposal_id), 2500);
+    
+    // 60% vested, 2500 already claimed
+    env.ledger().set_timestamp(2060);
+    assert_eq!(client.claim_stream(&proposal_id), 3500);
+    assert_eq!(client.get_proposal(&proposal_id).amount_disbursed, 6000);
+    
+    // Past the end everything has vested
+    env.ledger().set_timestamp(5000);
+    assert_eq!(client.claim_stream(&proposal_id), 4000);
+    assert_eq!(client.get_stream(&proposal_id).claimed, 10000);
+    
+    match client.get_proposal(&proposal_id).status {
+        ProposalStatus::Executed => {},
+        _ => panic!("Proposal should be executed"),
+    }
+}
+
+#[test]
+fn test_halt_stream_returns_unvested() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env).funded(20000);
+    let proposal_id = voted_proposal(&client, &members, submit_stream(&env, &client, &token), true);
+    
+    env.ledger().set_timestamp(2000);
+    client.execute_proposal(&proposal_id);
+    
+    env.ledger().set_timestamp(2040);
+    client.vote_halt(&members.get(0).unwrap(), &proposal_id);
+    client.vote_halt(&members.get(1).unwrap(), &proposal_id);
+    assert!(!client.get_stream(&proposal_id).halted);
+    client.vote_halt(&members.get(2).unwrap(), &proposal_id);
+    
+    // 40% vested before the halt; the rest returns to the treasury
+    let stream = client.get_stream(&proposal_id);
+    assert!(stream.halted);
+    assert_eq!(stream.amount, 4000);
+    assert_eq!(client.get_treasury_balance(&token), 16000);
+    
+    // Vesting stops, but the vested part stays claimable
+    env.ledger().set_timestamp(3000);
+    assert_eq!(client.claim_stream(&proposal_id), 4000);
+    
+    match client.get_proposal(&proposal_id).status {
+        ProposalStatus::Executed => {},
+        _ => panic!("Proposal should be executed"),
+    }
+}
+
+#[test]
+fn test_halt_needs_voting_threshold_of_members() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env).with_members(6).funded(20000);
+    let proposal_id = voted_proposal(&client, &members, submit_stream(&env, &client, &token), true);
+    
+    env.ledger().set_timestamp(2000);
+    client.execute_proposal(&proposal_id);
+    
+    // 66% of 6 members is 4 votes; 3 are not enough
+    for i in 0..3 {
+        client.vote_halt(&members.get(i).unwrap(), &proposal_id);
+    }
+    assert!(!client.get_stream(&proposal_id).halted);
+    
+    client.vote_halt(&members.get(3).unwrap(), &proposal_id);
+    assert!(client.get_stream(&proposal_id).halted);
+}
+
+#[test]
+#[should_panic(expected = "Nothing to claim")]
+fn test_claim_before_vesting() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env).funded(20000);
+    let proposal_id = voted_proposal(&client, &members, submit_stream(&env, &client, &token), true);
+    
+    env.ledger().set_timestamp(2000);
+    client.execute_proposal(&proposal_id);
+    client.claim_stream(&proposal_id); // Should panic
+}
+
+#[test]
+fn test_split_payouts_to_line_items() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env).funded(20000);
+    let pharmacy = Address::generate(&env);
+    let ambulance = Address::generate(&env);
+    client.register_provider(&admin, &pharmacy);
+    client.register_provider(&admin, &ambulance);
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Road accident"),
+        &symbol_short!("trauma"),
+        &token,
+        &vec![
+            &env,
+            LineItem { payee: hospital.clone(), amount: 6000, role: PayeeRole::Hospital },
+            LineItem { payee: pharmacy.clone(), amount: 3000, role: PayeeRole::Pharmacy },
+            LineItem { payee: ambulance.clone(), amount: 1000, role: PayeeRole::Ambulance },
+        ],
+        &Map::new(&env),
+    );
+    
+    let proposal = client.get_proposal(&proposal_id);
+    assert_eq!(proposal.amount_requested, 10000);
+    assert_eq!(proposal.line_items.len(), 3);
+    
+    // Approved at 5000, so every payee receives half of their line item
+    client.vote_with_amount(&members.get(0).unwrap(), &proposal_id, &5000);
+    client.vote_with_amount(&members.get(1).unwrap(), &proposal_id, &5000);
+    client.vote(&members.get(2).unwrap(), &proposal_id, &true);
+    client.finalize_proposal(&proposal_id);
+    client.execute_proposal(&proposal_id);
+    
+    assert_eq!(client.get_line_item_payouts(&proposal_id), vec![&env, 3000, 1500, 500]);
+    assert_eq!(client.get_treasury_balance(&token), 15000);
+    
+    // Payees pull their share
+    assert_eq!(client.get_claimable(&pharmacy, &token), 1500);
+    assert_eq!(client.claim(&pharmacy, &token), 1500);
+    assert_eq!(client.get_claimable(&pharmacy, &token), 0);
+    assert_eq!(TokenClient::new(&env, &token).balance(&pharmacy), 1500);
+    assert_eq!(client.get_claimable(&ambulance, &token), 500);
+}
+
+#[test]
+fn test_partial_payouts_add_up() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env).funded(1000);
+    let pharmacy = Address::generate(&env);
+    client.register_provider(&admin, &pharmacy);
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Treatment"),
+        &symbol_short!("trauma"),
+        &token,
+        &vec![
+            &env,
+            LineItem { payee: hospital.clone(), amount: 2000, role: PayeeRole::Hospital },
+            LineItem { payee: pharmacy.clone(), amount: 1000, role: PayeeRole::Pharmacy },
+        ],
+        &Map::new(&env),
+    );
+    voted_proposal(&client, &members, proposal_id, true);
+    
+    // 1000 of 3000 split 2:1, rounding remainder goes to the last item
+    client.execute_partial(&proposal_id);
+    assert_eq!(client.get_line_item_payouts(&proposal_id), vec![&env, 666, 334]);
+    
+    donate(&env, &client, &token, 2000, None);
+    client.execute_proposal(&proposal_id);
+    assert_eq!(client.get_line_item_payouts(&proposal_id), vec![&env, 2000, 1000]);
+}
+
+#[test]
+#[should_panic(expected = "Payee is not a registered provider")]
+fn test_unregistered_payee() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, .. } = TestDao::new(&env);
+    let stranger = Address::generate(&env);
+    
+    client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Treatment"),
+        &symbol_short!("trauma"),
+        &token,
+        &vec![&env, LineItem { payee: stranger, amount: 1000, role: PayeeRole::Family }],
+        &Map::new(&env),
+    ); // Should panic
+}
+
+#[test]
+fn test_expired_claims_return_to_treasury() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env).funded(20000);
+    let proposal_id = voted_proposal(&client, &members, submit_milestones(&env, &client, &token), true);
+    client.set_claim_period(&admin, &1000);
+    let reviewer = Address::generate(&env);
+    client.set_milestone_review(&admin, &1, &Some(reviewer.clone()));
+    
+    env.ledger().set_timestamp(100);
+    client.execute_proposal(&proposal_id);
+    client.submit_milestone_evidence(&hospital, &proposal_id, &0, &BytesN::from_array(&env, &[9; 32]));
+    client.confirm_milestone(&reviewer, &proposal_id, &0);
+    
+    assert_eq!(client.get_claimable(&hospital, &token), 4000);
+    assert_eq!(client.get_claim_expiry(&hospital, &token), 1100);
+    assert_eq!(client.get_treasury_balance(&token), 10000);
+    
+    // Unclaimed past the deadline, anyone can send it back to the treasury
+    env.ledger().set_timestamp(1101);
+    assert_eq!(client.reclaim_expired(&hospital, &token), 4000);
+    assert_eq!(client.get_claimable(&hospital, &token), 0);
+    assert_eq!(client.get_treasury_balance(&token), 14000);
+}
+
+#[test]
+fn test_payout_keeps_refund_claim_from_expiring() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env);
+    client.set_claim_period(&admin, &1000);
+    donate(&env, &client, &token, 5000, None);
+    
+    // The hospital's earmark for a rejected proposal is refunded without expiry
+    let rejected = submit_for_patient(&env, &client, &token, "ID-1001", 2000);
+    StellarAssetClient::new(&env, &token).mint(&hospital, &300);
+    client.add_funds(&hospital, &token, &300, &Some(Restriction::Proposal(rejected)));
+    for member in members.iter() {
+        client.vote(&member, &rejected, &false);
+    }
+    client.finalize_proposal(&rejected);
+    assert_eq!(client.get_claim_expiry(&hospital, &token), u64::MAX);
+    
+    // A later payout to the same address does not shorten the claim
+    let paid = voted_proposal(&client, &members, submit_in_category(&env, &client, &token, symbol_short!("trauma"), 1000), true);
+    client.execute_proposal(&paid);
+    assert_eq!(client.get_claim_expiry(&hospital, &token), u64::MAX);
+    
+    env.ledger().set_timestamp(1001);
+    assert_eq!(client.claim(&hospital, &token), 1300);
+}
+
+#[test]
+#[should_panic(expected = "Claim has expired")]
+fn test_claim_after_expiry() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env).funded(20000);
+    let proposal_id = voted_proposal(&client, &members, submit_stream(&env, &client, &token), true);
+    
+    env.ledger().set_timestamp(2000);
+    client.execute_proposal(&proposal_id);
+    env.ledger().set_timestamp(2100);
+    client.claim_stream(&proposal_id);
+    
+    env.ledger().set_timestamp(2100 + DEFAULT_CLAIM_PERIOD + 1);
+    client.claim(&hospital, &token); // Should panic
+}
+
+#[test]
+fn test_multi_asset_treasury() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token: usdc, members, .. } = TestDao::new(&env).funded(10000);
+    let eurc = create_treasury_token(&env, &client, &admin);
+    donate(&env, &client, &eurc, 4000, None);
+    
+    assert_eq!(client.get_treasury_tokens(), vec![&env, usdc.clone(), eurc.clone()]);
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Treatment"),
+        &symbol_short!("trauma"),
+        &eurc,
+        &single_payee(&env, &hospital, 3000),
+        &Map::new(&env),
+    );
+    voted_proposal(&client, &members, proposal_id, true);
+    client.execute_proposal(&proposal_id);
+    
+    // Only the requested asset is spent
+    assert_eq!(client.get_treasury_balance(&usdc), 10000);
+    assert_eq!(client.get_treasury_balance(&eurc), 1000);
+    
+    client.claim(&hospital, &eurc);
+    assert_eq!(TokenClient::new(&env, &eurc).balance(&hospital), 3000);
+    assert_eq!(TokenClient::new(&env, &usdc).balance(&hospital), 0);
+}
+
+/// Minimal SEP-40 style oracle that returns prices set by the test
+#[contract]
+pub struct MockOracle;
+
+#[contractimpl]
+impl MockOracle {
+    pub fn set_price(env: Env, asset: Asset, price: i128) {
+        let data = PriceData { price, timestamp: env.ledger().timestamp() };
+        env.storage().instance().set(&asset, &data);
+    }
+    
+    pub fn lastprice(env: Env, asset: Asset) -> Option<PriceData> {
+        env.storage().instance().get(&asset)
+    }
+}
+
+/// 1 token = 1.00 USD, 1 KES = 0.01 USD; prices valid for an hour, 2% slippage
+fn price_oracle<'a>(env: &'a Env, client: &EmergencyFundDAOClient, admin: &Address, token: &Address) -> MockOracleClient<'a> {
+    let oracle = MockOracleClient::new(env, &env.register(MockOracle, ()));
+    client.set_price_oracle(admin, &oracle.address, &3600, &200);
+    oracle.set_price(&Asset::Stellar(token.clone()), &10_000_000);
+    oracle.set_price(&Asset::Other(symbol_short!("KES")), &100_000);
+    oracle
+}
+
+fn submit_fiat(env: &Env, client: &EmergencyFundDAOClient, token: &Address) -> u64 {
+    let hospital = client.get_authorized_hospital();
+    client.submit_fiat_proposal(
+        &hospital,
+        &case_file(env, "Patient", "Appendectomy quoted in KES"),
+        &symbol_short!("trauma"),
+        token,
+        &symbol_short!("KES"),
+        &single_payee(env, &hospital, 500_000),
+        &Map::new(env),
+    )
+}
+
+#[test]
+fn test_fiat_proposal_converted_at_execution() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    env.ledger().set_timestamp(1000);
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env).funded(20000);
+    let oracle = price_oracle(&env, &client, &admin, &token);
+    let proposal_id = voted_proposal(&client, &members, submit_fiat(&env, &client, &token), true);
+    
+    let proposal = client.get_proposal(&proposal_id);
+    assert_eq!(proposal.fiat_currency, Some(symbol_short!("KES")));
+    assert_eq!(proposal.amount_approved, 500_000);
+    assert_eq!(proposal.fiat_quote, 5000);
+    
+    // The shilling strengthens 1% before execution, still within the cap
+    env.ledger().set_timestamp(2000);
+    oracle.set_price(&Asset::Stellar(token.clone()), &10_000_000);
+    oracle.set_price(&Asset::Other(symbol_short!("KES")), &101_000);
+    assert_eq!(client.quote_fiat(&token, &symbol_short!("KES"), &500_000), 5050);
+    
+    client.execute_proposal(&proposal_id);
+    assert_eq!(client.get_treasury_balance(&token), 14950);
+    assert_eq!(client.get_claimable(&hospital, &token), 5050);
+    assert_eq!(client.get_proposal(&proposal_id).amount_disbursed, 500_000);
+}
+
+#[test]
+#[should_panic(expected = "Price moved beyond slippage cap")]
+fn test_fiat_proposal_slippage_cap() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    env.ledger().set_timestamp(1000);
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env).funded(20000);
+    let oracle = price_oracle(&env, &client, &admin, &token);
+    let proposal_id = voted_proposal(&client, &members, submit_fiat(&env, &client, &token), true);
+    
+    // 10% move against the treasury
+    oracle.set_price(&Asset::Other(symbol_short!("KES")), &110_000);
+    client.execute_proposal(&proposal_id); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Oracle price is stale")]
+fn test_fiat_proposal_stale_price() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    env.ledger().set_timestamp(1000);
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env).funded(20000);
+    price_oracle(&env, &client, &admin, &token);
+    let proposal_id = voted_proposal(&client, &members, submit_fiat(&env, &client, &token), true);
+    
+    env.ledger().set_timestamp(1000 + 3601);
+    client.execute_proposal(&proposal_id); // Should panic
+}
+
+#[test]
+fn test_donation_receipts() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token: usdc, .. } = TestDao::new(&env);
+    let alice = Address::generate(&env);
+    let bob = Address::generate(&env);
+    let xlm = create_treasury_token(&env, &client, &admin);
+    StellarAssetClient::new(&env, &usdc).mint(&alice, &5000);
+    StellarAssetClient::new(&env, &usdc).mint(&bob, &5000);
+    StellarAssetClient::new(&env, &xlm).mint(&alice, &5000);
+    
+    env.ledger().set_timestamp(100);
+    assert_eq!(client.add_funds(&alice, &usdc, &1000, &None), 1);
+    env.ledger().set_timestamp(200);
+    assert_eq!(client.add_funds(&bob, &usdc, &2000, &None), 2);
+    assert_eq!(client.add_funds(&alice, &usdc, &500, &None), 3);
+    assert_eq!(client.add_funds(&alice, &xlm, &700, &None), 4);
+    
+    assert_eq!(client.get_donor_total(&alice, &usdc), 1500);
+    assert_eq!(client.get_donor_total(&alice, &xlm), 700);
+    assert_eq!(client.get_donor_total(&bob, &usdc), 2000);
+    assert_eq!(client.get_donor_donations(&alice), vec![&env, 1, 3, 4]);
+    assert_eq!(client.get_donor_count(), 2);
+    assert_eq!(client.get_donors(&1, &10), vec![&env, alice.clone(), bob.clone()]);
+    assert_eq!(client.get_donors(&2, &10), vec![&env, bob.clone()]);
+    
+    // Receipts are kept in persistent storage rather than the instance
+    let ttl = env.as_contract(&client.address, || {
+        env.storage().persistent().get_ttl(&DataKey::Receipt(ReceiptKey::Donation(1)))
+    });
+    assert!(ttl >= RECORD_TTL_THRESHOLD);
+    
+    let receipt = client.get_donation(&1);
+    assert_eq!(receipt.donor, alice);
+    assert_eq!(receipt.token, usdc);
+    assert_eq!(receipt.amount, 1000);
+    assert_eq!(receipt.timestamp, 100);
+    
+    // Paginate two at a time
+    let page1 = client.list_donations(&1, &2);
+    assert_eq!(page1.len(), 2);
+    assert_eq!(page1.get(1).unwrap().donor, bob);
+    let page2 = client.list_donations(&3, &2);
+    assert_eq!(page2.len(), 2);
+    assert_eq!(page2.get(1).unwrap().token, xlm);
+    assert_eq!(client.list_donations(&5, &2).len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Error(Auth, InvalidAction)")]
+fn test_add_funds_requires_donor_auth() {
+    let env = Env::default();
+    
+    env.mock_all_auths();
+    let TestDao { client, token, .. } = TestDao::new(&env);
+    let donor = Address::generate(&env);
+    StellarAssetClient::new(&env, &token).mint(&donor, &1000);
+    env.set_auths(&[]);
+    
+    client.add_funds(&donor, &token, &1000, &None); // Should panic without the donor's signature
+}
+
+#[test]
+fn test_restricted_funds_drawn_first() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let pediatric = Restriction::Category(symbol_short!("pediatric"));
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Child patient", "Pediatric surgery"),
+        &symbol_short!("pediatric"),
+        &token,
+        &single_payee(&env, &hospital, 5000),
+        &Map::new(&env),
+    );
+    
+    donate(&env, &client, &token, 10000, None);
+    donate(&env, &client, &token, 3000, Some(pediatric.clone()));
+    donate(&env, &client, &token, 2000, Some(Restriction::Hospital(hospital.clone())));
+    donate(&env, &client, &token, 1000, Some(Restriction::Proposal(proposal_id)));
+    
+    assert_eq!(client.get_treasury_balance(&token), 10000);
+    assert_eq!(client.get_restricted_balance(&token, &pediatric), 3000);
+    assert_eq!(client.get_restricted_funds(&token).len(), 3);
+    assert_eq!(client.get_available_funds(&proposal_id), 16000);
+    
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    client.execute_proposal(&proposal_id);
+    
+    // Proposal, hospital and then category funds cover the whole payout
+    assert_eq!(client.get_restricted_balance(&token, &Restriction::Proposal(proposal_id)), 0);
+    assert_eq!(client.get_restricted_balance(&token, &Restriction::Hospital(hospital.clone())), 0);
+    assert_eq!(client.get_restricted_balance(&token, &pediatric), 1000);
+    assert_eq!(client.get_treasury_balance(&token), 10000);
+}
+
+#[test]
+fn test_proposal_earmark_refunded_on_rejection() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 5000);
+    let donor = Address::generate(&env);
+    StellarAssetClient::new(&env, &token).mint(&donor, &1000);
+    client.add_funds(&donor, &token, &1000, &Some(Restriction::Proposal(proposal_id)));
+    
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &false);
+    }
+    client.finalize_proposal(&proposal_id);
+    
+    assert_eq!(client.get_restricted_balance(&token, &Restriction::Proposal(proposal_id)), 0);
+    assert_eq!(client.get_restricted_funds(&token).len(), 0);
+    assert_eq!(client.claim(&donor, &token), 1000);
+}
+
+#[test]
+fn test_proposal_earmark_released_after_expiry() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, .. } = TestDao::new(&env);
+    client.set_proposal_lifetime(&admin, &100);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 5000);
+    let donor = Address::generate(&env);
+    StellarAssetClient::new(&env, &token).mint(&donor, &1000);
+    client.add_funds(&donor, &token, &1000, &Some(Restriction::Proposal(proposal_id)));
+    assert!(client.try_release_proposal_earmark(&proposal_id).is_err());
+    
+    env.ledger().set_timestamp(101);
+    assert_eq!(client.release_proposal_earmark(&proposal_id), 1000);
+    assert_eq!(client.claim(&donor, &token), 1000);
+}
+
+#[test]
+#[should_panic(expected = "Proposal is not accepting contributions")]
+fn test_earmark_for_closed_proposal() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env);
+    let proposal_id = voted_proposal(&client, &members, submit_for_patient(&env, &client, &token, "ID-1001", 1000), false);
+    
+    donate(&env, &client, &token, 500, Some(Restriction::Proposal(proposal_id))); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Proposal not found")]
+fn test_earmark_for_missing_proposal() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, .. } = TestDao::new(&env);
+    donate(&env, &client, &token, 500, Some(Restriction::Proposal(7))); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Insufficient funds in treasury")]
+fn test_restricted_funds_not_used_for_other_categories() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env).funded(1000);
+    donate(&env, &client, &token, 5000, Some(Restriction::Category(symbol_short!("cardiac"))));
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Fracture"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 3000),
+        &Map::new(&env),
+    );
+    assert_eq!(client.get_available_funds(&proposal_id), 1000);
+    
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    client.execute_proposal(&proposal_id); // Should panic
+}
+
+fn contribute(env: &Env, client: &EmergencyFundDAOClient, token: &Address, proposal_id: u64, amount: i128) -> Address {
+    let donor = Address::generate(env);
+    StellarAssetClient::new(env, token).mint(&donor, &amount);
+    client.contribute_to_proposal(&donor, &proposal_id, &amount);
+    donor
+}
+
+#[test]
+fn test_crowdfunding_counts_toward_execution() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env).funded(2000);
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Public appeal"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 5000),
+        &Map::new(&env),
+    );
+    
+    let alice = contribute(&env, &client, &token, proposal_id, 2000);
+    contribute(&env, &client, &token, proposal_id, 1000);
+    assert_eq!(client.get_contribution(&proposal_id, &alice), 2000);
+    assert_eq!(client.get_crowdfund(&proposal_id).balance, 3000);
+    assert_eq!(client.get_available_funds(&proposal_id), 5000);
+    
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    client.execute_proposal(&proposal_id);
+    
+    assert_eq!(client.get_crowdfund(&proposal_id).balance, 0);
+    assert_eq!(client.get_treasury_balance(&token), 0);
+    assert_eq!(client.get_claimable(&hospital, &token), 5000);
+}
+
+#[test]
+fn test_contributions_refunded_after_rejection() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Public appeal"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 5000),
+        &Map::new(&env),
+    );
+    
+    let alice = contribute(&env, &client, &token, proposal_id, 3000);
+    let bob = contribute(&env, &client, &token, proposal_id, 1000);
+    
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &false);
+    }
+    client.finalize_proposal(&proposal_id);
+    
+    assert_eq!(client.refund_contribution(&alice, &proposal_id), 3000);
+    assert_eq!(client.refund_contribution(&bob, &proposal_id), 1000);
+    
+    let token_client = TokenClient::new(&env, &token);
+    assert_eq!(token_client.balance(&alice), 3000);
+    assert_eq!(token_client.balance(&bob), 1000);
+    assert_eq!(client.get_crowdfund(&proposal_id).balance, 0);
+    assert_eq!(client.get_contribution(&proposal_id, &alice), 0);
+}
+
+#[test]
+fn test_leftover_escrow_shared_after_execution() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Public appeal"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 4000),
+        &Map::new(&env),
+    );
+    
+    let alice = contribute(&env, &client, &token, proposal_id, 3000);
+    let bob = contribute(&env, &client, &token, proposal_id, 1000);
+    
+    // Approved for half of the request
+    client.vote_with_amount(&members.get(0).unwrap(), &proposal_id, &2000);
+    client.vote_with_amount(&members.get(1).unwrap(), &proposal_id, &2000);
+    client.vote(&members.get(2).unwrap(), &proposal_id, &true);
+    client.finalize_proposal(&proposal_id);
+    client.execute_proposal(&proposal_id);
+    
+    // The unused half of the escrow goes back pro rata
+    assert_eq!(client.refund_contribution(&alice, &proposal_id), 1500);
+    assert_eq!(client.refund_contribution(&bob, &proposal_id), 500);
+    assert_eq!(client.get_crowdfund(&proposal_id).balance, 0);
+}
+
+#[test]
+fn test_contributions_refunded_after_expiry() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, .. } = TestDao::new(&env);
+    client.set_proposal_lifetime(&admin, &100);
+    
+    env.ledger().set_timestamp(1000);
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Public appeal"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 4000),
+        &Map::new(&env),
+    );
+    let alice = contribute(&env, &client, &token, proposal_id, 2500);
+    
+    env.ledger().set_timestamp(1101);
+    assert_eq!(client.refund_contribution(&alice, &proposal_id), 2500);
+    assert_eq!(TokenClient::new(&env, &token).balance(&alice), 2500);
+}
+
+#[test]
+#[should_panic(expected = "Proposal is still open for funding")]
+fn test_refund_while_proposal_open() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, .. } = TestDao::new(&env);
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Public appeal"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 4000),
+        &Map::new(&env),
+    );
+    let alice = contribute(&env, &client, &token, proposal_id, 1000);
+    
+    client.refund_contribution(&alice, &proposal_id); // Should panic
+}
+
+#[test]
+fn test_refund_waits_for_escrow_to_settle() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env).funded(5000);
+    let proposal_id = submit_stream(&env, &client, &token);
+    let alice = contribute(&env, &client, &token, proposal_id, 3000);
+    let bob = contribute(&env, &client, &token, proposal_id, 2000);
+    voted_proposal(&client, &members, proposal_id, true);
+    
+    env.ledger().set_timestamp(2000);
+    client.execute_proposal(&proposal_id);
+    assert_eq!(client.get_crowdfund(&proposal_id).balance, 0);
+    assert!(client.try_refund_contribution(&alice, &proposal_id).is_err());
+    
+    // A quarter vested; the rest returns to general funds first, then the crowdfund
+    env.ledger().set_timestamp(2025);
+    client.cancel_proposal(&admin, &proposal_id);
+    assert_eq!(client.get_treasury_balance(&token), 5000);
+    assert_eq!(client.get_crowdfund(&proposal_id).balance, 2500);
+    
+    assert_eq!(client.refund_contribution(&alice, &proposal_id), 1500);
+    assert_eq!(client.refund_contribution(&bob, &proposal_id), 1000);
+    assert_eq!(client.get_crowdfund(&proposal_id).balance, 0);
+}
+
+fn create_pool(env: &Env, client: &EmergencyFundDAOClient, token: &Address, ratio_bps: u32, cap: i128, restriction: Option<Restriction>) -> (Address, u64) {
+    let sponsor = Address::generate(env);
+    StellarAssetClient::new(env, token).mint(&sponsor, &cap);
+    let pool_id = client.create_matching_pool(&sponsor, token, &ratio_bps, &cap, &1000, &restriction);
+    (sponsor, pool_id)
+}
+
+#[test]
+fn test_matching_pool_matches_donations_until_cap() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, .. } = TestDao::new(&env);
+    let (sponsor, pool_id) = create_pool(&env, &client, &token, 10_000, 1500, None);
+    assert_eq!(client.get_active_pools(), vec![&env, pool_id]);
+    
+    donate(&env, &client, &token, 1000, None);
+    assert_eq!(client.get_treasury_balance(&token), 2000);
+    assert_eq!(client.get_pool_remaining(&pool_id), 500);
+    assert_eq!(client.get_donor_total(&sponsor, &token), 1000);
+    
+    // Only what is left in the pool gets matched
+    donate(&env, &client, &token, 1000, None);
+    assert_eq!(client.get_treasury_balance(&token), 3500);
+    assert_eq!(client.get_pool_remaining(&pool_id), 0);
+}
+
+#[test]
+fn test_restricted_matching_pool() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, .. } = TestDao::new(&env);
+    let pediatric = Restriction::Category(symbol_short!("pediatric"));
+    let (sponsor, pool_id) = create_pool(&env, &client, &token, 5_000, 5000, Some(pediatric.clone()));
+    
+    // Unrestricted donations are not matched by a pediatric pool
+    donate(&env, &client, &token, 1000, None);
+    assert_eq!(client.get_pool_remaining(&pool_id), 5000);
+    
+    donate(&env, &client, &token, 1000, Some(pediatric.clone()));
+    assert_eq!(client.get_restricted_balance(&token, &pediatric), 1500);
+    
+    // Contributions to a pediatric proposal are matched into its escrow
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Child patient", "Pediatric surgery"),
+        &symbol_short!("pediatric"),
+        &token,
+        &single_payee(&env, &hospital, 4000),
+        &Map::new(&env),
+    );
+    contribute(&env, &client, &token, proposal_id, 2000);
+    assert_eq!(client.get_crowdfund(&proposal_id).balance, 3000);
+    assert_eq!(client.get_contribution(&proposal_id, &sponsor), 1000);
+    assert_eq!(client.get_pool_remaining(&pool_id), 3500);
+}
+
+#[test]
+fn test_close_expired_matching_pool() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, .. } = TestDao::new(&env);
+    let (sponsor, pool_id) = create_pool(&env, &client, &token, 10_000, 3000, None);
+    donate(&env, &client, &token, 1000, None);
+    
+    env.ledger().set_timestamp(1001);
+    assert_eq!(client.get_pool_remaining(&pool_id), 0);
+    
+    // Expired pools stop matching
+    donate(&env, &client, &token, 1000, None);
+    assert_eq!(client.get_treasury_balance(&token), 3000);
+    
+    assert_eq!(client.close_matching_pool(&sponsor, &pool_id), 2000);
+    assert_eq!(TokenClient::new(&env, &token).balance(&sponsor), 2000);
+    assert_eq!(client.get_active_pools().len(), 0);
+}
+
+#[test]
+#[should_panic(expected = "Matching pool has not expired")]
+fn test_close_matching_pool_before_expiry() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, .. } = TestDao::new(&env);
+    let (sponsor, pool_id) = create_pool(&env, &client, &token, 10_000, 3000, None);
+    
+    client.close_matching_pool(&sponsor, &pool_id); // Should panic
+}
+
+fn dissolve(client: &EmergencyFundDAOClient, members: &Vec<Address>, grace_period: u64) {
+    client.propose_dissolution(&members.get(0).unwrap(), &grace_period);
+    for member in members.iter() {
+        client.vote_dissolution(&member, &true);
+    }
+    assert_eq!(client.finalize_dissolution(), DissolutionStatus::Approved);
+}
+
+#[test]
+fn test_dissolution_refunds_remaining_treasury() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let donors = vec![&env, Address::generate(&env), Address::generate(&env), Address::generate(&env)];
+    let gifts = [3000_i128, 2000, 2000];
+    for (i, donor) in donors.iter().enumerate() {
+        StellarAssetClient::new(&env, &token).mint(&donor, &gifts[i]);
+    }
+    client.add_funds(&donors.get(0).unwrap(), &token, &3000, &None);
+    client.add_funds(&donors.get(1).unwrap(), &token, &2000, &None);
+    client.add_funds(&donors.get(2).unwrap(), &token, &2000, &Some(Restriction::Category(symbol_short!("pediatric"))));
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Emergency surgery"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::new(&env),
+    );
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    
+    env.ledger().set_timestamp(100);
+    dissolve(&client, &members, 50);
+    
+    // Approved proposals can still be executed during the grace period
+    client.execute_proposal(&proposal_id);
+    
+    env.ledger().set_timestamp(151);
+    let remaining = client.distribute_remaining(&token);
+    assert_eq!(remaining, 6000);
+    assert_eq!(client.get_treasury_balance(&token), 0);
+    
+    // The unspent 4000 of general funds is shared 3:2; the earmarked 2000
+    // was never spent and goes back in full to the donor who restricted it
+    let pediatric = Some(Restriction::Category(symbol_short!("pediatric")));
+    assert_eq!(client.get_distribution_owed(&donors.get(0).unwrap(), &token, &None), 2400);
+    assert_eq!(client.get_distribution_owed(&donors.get(0).unwrap(), &token, &pediatric), 0);
+    let refunded = client.claim_distribution(&donors.get(0).unwrap(), &token, &None)
+        + client.claim_distribution(&donors.get(1).unwrap(), &token, &None)
+        + client.claim_distribution(&donors.get(2).unwrap(), &token, &pediatric);
+    assert_eq!(refunded, remaining);
+    assert_eq!(client.get_distribution_owed(&donors.get(0).unwrap(), &token, &None), 0);
+    assert_eq!(TokenClient::new(&env, &token).balance(&donors.get(0).unwrap()), 2400);
+    assert_eq!(TokenClient::new(&env, &token).balance(&donors.get(1).unwrap()), 1600);
+    assert_eq!(TokenClient::new(&env, &token).balance(&donors.get(2).unwrap()), 2000);
+}
+
+#[test]
+fn test_dissolution_shares_surplus_with_general_donors() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env);
+    let donors = vec![&env, Address::generate(&env), Address::generate(&env)];
+    for donor in donors.iter() {
+        StellarAssetClient::new(&env, &token).mint(&donor, &1000);
+        client.add_funds(&donor, &token, &1000, &None);
+    }
+    StellarAssetClient::new(&env, &token).mint(&client.address, &500);
+    client.reconcile(&token);
+    
+    dissolve(&client, &members, 50);
+    env.ledger().set_timestamp(51);
+    assert_eq!(client.distribute_remaining(&token), 2500);
+    
+    // Donors claim their part one by one, once
+    let first = donors.get(0).unwrap();
+    assert_eq!(client.claim_distribution(&first, &token, &None), 1250);
+    assert!(client.try_claim_distribution(&first, &token, &None).is_err());
+    assert_eq!(client.get_distribution_owed(&donors.get(1).unwrap(), &token, &None), 1250);
+    
+    // Funds that come back later are shared the same way
+    StellarAssetClient::new(&env, &token).mint(&client.address, &100);
+    client.reconcile(&token);
+    assert_eq!(client.distribute_remaining(&token), 100);
+    assert_eq!(client.claim_distribution(&first, &token, &None), 50);
+    assert_eq!(client.get_distribution_owed(&donors.get(1).unwrap(), &token, &None), 1300);
+}
+
+#[test]
+#[should_panic(expected = "DAO is dissolving")]
+fn test_no_new_proposals_while_dissolving() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    dissolve(&client, &members, 50);
+    
+    client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Emergency surgery"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::new(&env),
+    ); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Proposal has expired")]
+fn test_execution_blocked_after_grace_period() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env).funded(5000);
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Emergency surgery"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::new(&env),
+    );
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    dissolve(&client, &members, 50);
+    
+    env.ledger().set_timestamp(51);
+    client.execute_proposal(&proposal_id); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Grace period has not ended")]
+fn test_distribute_during_grace_period() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env).funded(5000);
+    dissolve(&client, &members, 50);
+    
+    client.distribute_remaining(&token); // Should panic
+}
+
+#[test]
+fn test_rejected_dissolution_keeps_dao_running() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env);
+    client.propose_dissolution(&members.get(0).unwrap(), &50);
+    for member in members.iter() {
+        client.vote_dissolution(&member, &false);
+    }
+    assert_eq!(client.finalize_dissolution(), DissolutionStatus::Rejected);
+    
+    donate(&env, &client, &token, 1000, None);
+    assert_eq!(client.get_treasury_balance(&token), 1000);
+    assert_eq!(client.propose_dissolution(&members.get(1).unwrap(), &50), 2);
+}
+
+#[test]
+fn test_reconcile_records_direct_transfers() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, .. } = TestDao::new(&env).funded(1000);
+    assert_eq!(client.reconcile(&token), 0);
+    
+    // Tokens sent straight to the contract bypass `add_funds`
+    StellarAssetClient::new(&env, &token).mint(&client.address, &250);
+    assert_eq!(client.reconcile(&token), 250);
+    let events = env.events().all();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events.get(0).unwrap().0, client.address);
+    assert_eq!(client.get_treasury_balance(&token), 1250);
+    assert_eq!(client.get_donor_total(&client.address, &token), 0);
+    assert_eq!(client.get_donor_count(), 1);
+    assert_eq!(client.reconcile(&token), 0);
+}
+
+#[test]
+fn test_sweep_stray_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, .. } = TestDao::new(&env);
+    let stray = env.register_stellar_asset_contract_v2(admin.clone()).address();
+    StellarAssetClient::new(&env, &stray).mint(&client.address, &400);
+    
+    let recipient = Address::generate(&env);
+    assert_eq!(client.sweep(&admin, &stray, &recipient), 400);
+    assert_eq!(TokenClient::new(&env, &stray).balance(&recipient), 400);
+}
+
+#[test]
+#[should_panic(expected = "Cannot sweep a treasury asset")]
+fn test_sweep_treasury_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, .. } = TestDao::new(&env).funded(1000);
+    
+    client.sweep(&admin, &token, &admin); // Should panic
+}
+
+#[contract]
+pub struct MockVault;
+
+#[contractimpl]
+impl MockVault {
+    pub fn __constructor(env: Env, token: Address) {
+        env.storage().instance().set(&symbol_short!("token"), &token);
+    }
+    
+    pub fn deposit(env: Env, from: Address, amount: i128) {
+        from.require_auth();
+        let token: Address = env.storage().instance().get(&symbol_short!("token")).unwrap();
+        TokenClient::new(&env, &token).transfer(&from, env.current_contract_address(), &amount);
+        Self::accrue(env, from, amount);
+    }
+    
+    pub fn withdraw(env: Env, to: Address, amount: i128) {
+        to.require_auth();
+        let token: Address = env.storage().instance().get(&symbol_short!("token")).unwrap();
+        TokenClient::new(&env, &token).transfer(&env.current_contract_address(), &to, &amount);
+        Self::accrue(env, to, -amount);
+    }
+    
+    pub fn balance(env: Env, owner: Address) -> i128 {
+        env.storage().instance().get(&owner).unwrap_or(0)
+    }
+    
+    /// Credit yield to an owner (tokens are minted to the vault separately)
+    pub fn accrue(env: Env, owner: Address, amount: i128) {
+        let balance = Self::balance(env.clone(), owner.clone());
+        env.storage().instance().set(&owner, &(balance + amount));
+    }
+}
+
+/// Invest 8000 in a mock vault, keeping a 2000 liquidity floor
+fn invest_in_vault<'a>(env: &'a Env, client: &EmergencyFundDAOClient, admin: &Address, token: &Address) -> MockVaultClient<'a> {
+    let vault = MockVaultClient::new(env, &env.register(MockVault, (token,)));
+    client.set_vault(admin, token, &vault.address, &2000);
+    client.deposit_to_vault(admin, token, &8000);
+    vault
+}
+
+#[test]
+fn test_execution_withdraws_shortfall_from_vault() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env).funded(10000);
+    let vault = invest_in_vault(&env, &client, &admin, &token);
+    assert_eq!(client.get_liquid_balance(&token), 2000);
+    assert_eq!(TokenClient::new(&env, &token).balance(&vault.address), 8000);
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Emergency surgery"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 5000),
+        &Map::new(&env),
+    );
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    client.execute_proposal(&proposal_id);
+    
+    // The shortfall and the liquidity floor come back from the vault
+    assert_eq!(client.get_treasury_balance(&token), 5000);
+    assert_eq!(client.get_liquid_balance(&token), 2000);
+    assert_eq!(client.get_vault(&token).principal, 3000);
+    assert_eq!(client.claim(&hospital, &token), 5000);
+    assert_eq!(client.reconcile(&token), 0);
+}
+
+#[test]
+fn test_vault_yield_goes_to_general_funds() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, .. } = TestDao::new(&env).funded(10000);
+    let vault = invest_in_vault(&env, &client, &admin, &token);
+    StellarAssetClient::new(&env, &token).mint(&vault.address, &300);
+    vault.accrue(&client.address, &300);
+    
+    assert_eq!(client.harvest_yield(&token), 300);
+    assert_eq!(client.get_treasury_balance(&token), 10300);
+    assert_eq!(client.get_liquid_balance(&token), 2300);
+    assert_eq!(client.get_vault(&token).principal, 8000);
+    
+    client.withdraw_from_vault(&admin, &token, &8000);
+    assert_eq!(client.get_liquid_balance(&token), 10300);
+    assert_eq!(client.reconcile(&token), 0);
+}
+
+#[test]
+#[should_panic(expected = "Deposit would breach the liquidity floor")]
+fn test_vault_deposit_respects_liquidity_floor() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, .. } = TestDao::new(&env).funded(10000);
+    invest_in_vault(&env, &client, &admin, &token);
+    client.deposit_to_vault(&admin, &token, &1); // Should panic
+}
+
+
+#[test]
+fn test_vault_deposit_authorized_by_dao() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, .. } = TestDao::new(&env).funded(10000);
+    let vault = invest_in_vault(&env, &client, &admin, &token);
+    client.set_vault(&admin, &token, &vault.address, &1000);
+    
+    // Only the admin signs; the DAO authorizes the vault's pull itself
+    env.mock_auths(&[MockAuth {
+        address: &admin,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "deposit_to_vault",
+            args: (&admin, &token, 1000_i128).into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    client.deposit_to_vault(&admin, &token, &1000);
+    assert_eq!(vault.balance(&client.address), 9000);
+}
+
+fn submit_in_category(env: &Env, client: &EmergencyFundDAOClient, token: &Address, category: Symbol, amount: i128) -> u64 {
+    let hospital = client.get_authorized_hospital();
+    client.submit_proposal(
+        &hospital,
+        &case_file(env, "Patient", "Emergency care"),
+        &category,
+        token,
+        &single_payee(env, &hospital, amount),
+        &Map::new(env),
+    )
+}
+
+fn quarterly_budget(env: &Env, client: &EmergencyFundDAOClient, admin: &Address, token: &Address, start: u64, rollover: bool) -> u32 {
+    let allocations = Map::from_array(env, [
+        (symbol_short!("trauma"), 3000_i128),
+        (symbol_short!("cardiac"), 2000_i128),
+    ]);
+    client.add_budget_epoch(admin, token, &start, &(start + 1000), &6000, &allocations, &rollover)
+}
+
+#[test]
+fn test_cancelled_escrow_returns_to_its_sources() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env);
+    let trauma = Restriction::Category(symbol_short!("trauma"));
+    donate(&env, &client, &token, 5000, None);
+    donate(&env, &client, &token, 1500, Some(trauma.clone()));
+    quarterly_budget(&env, &client, &admin, &token, 0, false);
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Dialysis"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 3000),
+        &Map::new(&env),
+    );
+    let alice = contribute(&env, &client, &token, proposal_id, 500);
+    client.request_stream(&hospital, &proposal_id, &100);
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    client.execute_proposal(&proposal_id);
+    assert_eq!(client.get_restricted_balance(&token, &trauma), 0);
+    assert_eq!(client.get_treasury_balance(&token), 4000);
+    assert_eq!(client.get_budget_remaining(&token, &symbol_short!("trauma")), Some(0));
+    
+    // Nothing has vested, so every pot gets back what was drawn from it
+    client.cancel_proposal(&admin, &proposal_id);
+    assert_eq!(client.get_restricted_balance(&token, &trauma), 1500);
+    assert_eq!(client.get_treasury_balance(&token), 5000);
+    assert_eq!(client.get_budget_remaining(&token, &symbol_short!("trauma")), Some(3000));
+    assert_eq!(client.refund_contribution(&alice, &proposal_id), 500);
+}
+
+#[test]
+fn test_execution_charges_category_budget() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env).funded(20000);
+    quarterly_budget(&env, &client, &admin, &token, 0, false);
+    assert_eq!(client.get_current_epoch(&token), Some(1));
+    
+    let trauma = voted_proposal(&client, &members, submit_in_category(&env, &client, &token, symbol_short!("trauma"), 2000), true);
+    client.execute_proposal(&trauma);
+    assert_eq!(client.get_budget_remaining(&token, &symbol_short!("trauma")), Some(1000));
+    
+    // Categories without an allocation share the unallocated 1000
+    let maternal = voted_proposal(&client, &members, submit_in_category(&env, &client, &token, symbol_short!("maternal"), 1500), true);
+    assert_eq!(client.execute_partial(&maternal), 1000);
+    assert_eq!(client.get_budget_remaining(&token, &symbol_short!("maternal")), Some(0));
+    assert_eq!(client.get_budget_epoch(&token, &1).reserve_spent, 1000);
+    
+    // Spending is not budgeted once the epoch is over
+    env.ledger().set_timestamp(1000);
+    assert_eq!(client.get_current_epoch(&token), None);
+    client.execute_proposal(&maternal);
+}
+
+#[test]
+#[should_panic(expected = "Category budget exceeded")]
+fn test_execution_over_category_budget() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env).funded(20000);
+    quarterly_budget(&env, &client, &admin, &token, 0, false);
+    
+    let cardiac = voted_proposal(&client, &members, submit_in_category(&env, &client, &token, symbol_short!("cardiac"), 2500), true);
+    client.execute_proposal(&cardiac); // Should panic
+}
+
+#[test]
+fn test_budgets_are_kept_per_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env).funded(20000);
+    let other = create_treasury_token(&env, &client, &admin);
+    donate(&env, &client, &other, 20000, None);
+    quarterly_budget(&env, &client, &admin, &token, 0, false);
+    
+    // Spending in another token is not charged to this token's epoch
+    let trauma = voted_proposal(&client, &members, submit_in_category(&env, &client, &other, symbol_short!("trauma"), 5000), true);
+    client.execute_proposal(&trauma);
+    assert_eq!(client.get_budget_remaining(&token, &symbol_short!("trauma")), Some(3000));
+    assert_eq!(client.get_budget_remaining(&other, &symbol_short!("trauma")), None);
+    
+    let trauma = voted_proposal(&client, &members, submit_in_category(&env, &client, &token, symbol_short!("trauma"), 2000), true);
+    client.execute_proposal(&trauma);
+    assert_eq!(client.get_budget_remaining(&token, &symbol_short!("trauma")), Some(1000));
+}
+
+#[test]
+fn test_unspent_allocations_roll_over() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env).funded(20000);
+    quarterly_budget(&env, &client, &admin, &token, 0, true);
+    quarterly_budget(&env, &client, &admin, &token, 1000, false);
+    quarterly_budget(&env, &client, &admin, &token, 2000, false);
+    
+    let trauma = voted_proposal(&client, &members, submit_in_category(&env, &client, &token, symbol_short!("trauma"), 1000), true);
+    client.execute_proposal(&trauma);
+    
+    env.ledger().set_timestamp(1500);
+    assert_eq!(client.get_budget_epoch(&token, &2).carried.get(symbol_short!("trauma")), Some(2000));
+    assert_eq!(client.get_budget_remaining(&token, &symbol_short!("trauma")), Some(5000));
+    assert_eq!(client.get_budget_remaining(&token, &symbol_short!("cardiac")), Some(4000));
+    
+    // The second epoch does not roll over
+    env.ledger().set_timestamp(2500);
+    assert_eq!(client.get_budget_remaining(&token, &symbol_short!("trauma")), Some(3000));
+}
+
+#[test]
+fn test_verify_patient_commitment() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, .. } = TestDao::new(&env);
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Sarah Martinez", "Bypass surgery"),
+        &symbol_short!("cardiac"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::new(&env),
+    );
+    
+    let proposal = client.get_proposal(&proposal_id);
+    assert_eq!(proposal.case_file.uri, String::from_str(&env, "ipfs://encrypted-case-file"));
+    
+    let name = String::from_str(&env, "Sarah Martinez");
+    assert!(client.verify_patient(&proposal_id, &name, &patient_salt(&env)));
+    assert!(!client.verify_patient(&proposal_id, &name, &BytesN::from_array(&env, &[8; 32])));
+    assert!(!client.verify_patient(&proposal_id, &String::from_str(&env, "Sara Martinez"), &patient_salt(&env)));
+}
+
+fn envelope(env: &Env, seed: u8) -> Bytes {
+    Bytes::from_array(env, &[seed; 48])
+}
+
+#[test]
+fn test_case_file_envelopes() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env);
+    let alice = members.get(0).unwrap();
+    let bob = members.get(1).unwrap();
+    client.set_encryption_key(&alice, &BytesN::from_array(&env, &[1; 32]));
+    client.set_encryption_key(&bob, &BytesN::from_array(&env, &[2; 32]));
+    
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Emergency surgery"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::from_array(&env, [(alice.clone(), envelope(&env, 1)), (bob.clone(), envelope(&env, 2))]),
+    );
+    assert_eq!(client.get_my_envelope(&proposal_id, &alice), envelope(&env, 1));
+    assert_eq!(client.get_my_envelope(&proposal_id, &bob), envelope(&env, 2));
+    
+    // A member who joins later receives the key from the hospital
+    let carol = Address::generate(&env);
+    client.add_member(&admin, &carol);
+    client.set_encryption_key(&carol, &BytesN::from_array(&env, &[3; 32]));
+    client.share_envelope(&hospital, &proposal_id, &carol, &envelope(&env, 3));
+    assert_eq!(client.get_my_envelope(&proposal_id, &carol), envelope(&env, 3));
+}
+
+#[test]
+#[should_panic(expected = "Member has no encryption key")]
+fn test_envelope_requires_registered_key() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Emergency surgery"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::from_array(&env, [(members.get(0).unwrap(), envelope(&env, 1))]),
+    ); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Only the submitting hospital can share envelopes")]
+fn test_share_envelope_only_by_hospital() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let alice = members.get(0).unwrap();
+    client.set_encryption_key(&alice, &BytesN::from_array(&env, &[1; 32]));
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "Patient", "Emergency surgery"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::new(&env),
+    );
+    
+    client.share_envelope(&alice, &proposal_id, &alice, &envelope(&env, 1)); // Should panic
+}
+
+#[test]
+fn test_erase_patient_data_after_retention() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env).funded(5000);
+    client.set_retention_period(&admin, &100);
+    let alice = members.get(0).unwrap();
+    client.set_encryption_key(&alice, &BytesN::from_array(&env, &[1; 32]));
+    
+    let case = case_file(&env, "Patient", "Emergency care");
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case,
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::from_array(&env, [(alice.clone(), envelope(&env, 1))]),
+    );
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    env.ledger().set_timestamp(50);
+    client.execute_proposal(&proposal_id);
+    assert_eq!(client.get_proposal(&proposal_id).closed_at, 50);
+    
+    env.ledger().set_timestamp(150);
+    let tombstone = client.erase_patient_data(&hospital, &proposal_id);
+    assert_eq!(tombstone, env.crypto().sha256(&case.to_xdr(&env)).to_bytes());
+    assert_eq!(client.get_patient_proposals(&patient_id(&env, "Patient")), Vec::new(&env));
+    
+    // The financial record is kept
+    let proposal = client.get_proposal(&proposal_id);
+    assert_eq!(proposal.tombstone, Some(tombstone));
+    assert_eq!(proposal.case_file.uri, String::from_str(&env, ""));
+    assert_eq!(proposal.amount_disbursed, 1000);
+    assert_eq!(proposal.votes_for, 3);
+    
+    let result = client.try_get_my_envelope(&proposal_id, &alice);
+    assert!(result.is_err());
+}
+
+#[test]
+#[should_panic(expected = "Retention period has not passed")]
+fn test_erase_patient_data_kept_forever() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env);
+    client.set_retention_period(&admin, &u64::MAX);
+    let proposal_id = voted_proposal(&client, &members, submit_in_category(&env, &client, &token, symbol_short!("trauma"), 1000), false);
+    
+    env.ledger().set_timestamp(u64::MAX - 1);
+    client.erase_patient_data(&admin, &proposal_id); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Retention period has not passed")]
+fn test_erase_patient_data_during_retention() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env);
+    let proposal_id = client.submit_proposal(
+        &client.get_authorized_hospital(),
+        &case_file(&env, "Patient", "Emergency care"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &client.get_authorized_hospital(), 1000),
+        &Map::new(&env),
+    );
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &false);
+    }
+    client.finalize_proposal(&proposal_id);
+    
+    client.erase_patient_data(&admin, &proposal_id); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Proposal is not closed")]
+fn test_erase_patient_data_of_open_proposal() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env);
+    client.set_retention_period(&admin, &0);
+    let proposal_id = voted_proposal(&client, &members, submit_in_category(&env, &client, &token, symbol_short!("trauma"), 1000), true);
+    
+    client.erase_patient_data(&admin, &proposal_id); // Should panic
+}
+
+fn submit_for_patient(env: &Env, client: &EmergencyFundDAOClient, token: &Address, patient: &str, amount: i128) -> u64 {
+    let hospital = client.get_authorized_hospital();
+    client.submit_proposal(
+        &hospital,
+        &case_file(env, patient, "Treatment"),
+        &symbol_short!("trauma"),
+        token,
+        &single_payee(env, &hospital, amount),
+        &Map::new(env),
+    )
+}
+
+#[test]
+fn test_patient_funding_tracked_across_proposals() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env);
+    client.set_patient_caps(&admin, &Denomination::Token(token.clone()), &5000, &0, &0, &false);
+    
+    let first = submit_for_patient(&env, &client, &token, "ID-1001", 3000);
+    let rejected = submit_for_patient(&env, &client, &token, "ID-1001", 1500);
+    for member in members.iter() {
+        client.vote(&member, &rejected, &false);
+    }
+    client.finalize_proposal(&rejected);
+    
+    // Rejected proposals do not count toward the patient's funding
+    assert_eq!(client.get_patient_funding_total(&patient_id(&env, "ID-1001"), &Denomination::Token(token.clone())), 3000);
+    assert_eq!(client.get_patient_proposals(&patient_id(&env, "ID-1001")), vec![&env, first, rejected]);
+    
+    let within = submit_for_patient(&env, &client, &token, "ID-1001", 2000);
+    assert!(!client.get_proposal(&within).over_patient_cap);
+    let over = submit_for_patient(&env, &client, &token, "ID-1001", 1);
+    assert!(client.get_proposal(&over).over_patient_cap);
+    
+    // Other patients are unaffected
+    let other = submit_for_patient(&env, &client, &token, "ID-2002", 4000);
+    assert!(!client.get_proposal(&other).over_patient_cap);
+}
+
+#[test]
+fn test_patient_caps_per_denomination() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, .. } = TestDao::new(&env);
+    let other = create_treasury_token(&env, &client, &admin);
+    client.set_patient_caps(&admin, &Denomination::Token(token.clone()), &5000, &0, &0, &false);
+    
+    // Requests in another token are neither capped nor counted against this cap
+    let elsewhere = submit_for_patient(&env, &client, &other, "ID-1001", 6000);
+    assert!(!client.get_proposal(&elsewhere).over_patient_cap);
+    let within = submit_for_patient(&env, &client, &token, "ID-1001", 5000);
+    assert!(!client.get_proposal(&within).over_patient_cap);
+    
+    assert_eq!(client.get_patient_funding_total(&patient_id(&env, "ID-1001"), &Denomination::Token(token.clone())), 5000);
+    assert_eq!(client.get_patient_funding_total(&patient_id(&env, "ID-1001"), &Denomination::Token(other)), 6000);
+}
+
+#[test]
+fn test_patient_period_cap() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, .. } = TestDao::new(&env);
+    client.set_patient_caps(&admin, &Denomination::Token(token.clone()), &0, &2000, &100, &true);
+    
+    submit_for_patient(&env, &client, &token, "ID-1001", 2000);
+    env.ledger().set_timestamp(101);
+    submit_for_patient(&env, &client, &token, "ID-1001", 2000);
+    assert_eq!(client.get_patient_funding_total(&patient_id(&env, "ID-1001"), &Denomination::Token(token.clone())), 4000);
+}
+
+#[test]
+#[should_panic(expected = "Patient funding cap exceeded")]
+fn test_patient_cap_rejects_proposal() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, .. } = TestDao::new(&env);
+    client.set_patient_caps(&admin, &Denomination::Token(token.clone()), &0, &2000, &100, &true);
+    
+    submit_for_patient(&env, &client, &token, "ID-1001", 1500);
+    env.ledger().set_timestamp(100);
+    submit_for_patient(&env, &client, &token, "ID-1001", 1000); // Should panic
+}
+
+fn document_hash(env: &Env, document: &str) -> BytesN<32> {
+    env.crypto().sha256(&Bytes::from_slice(env, document.as_bytes())).to_bytes()
+}
+
+#[test]
+fn test_attachments_versioned_per_vote() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    
+    let version = client.add_attachment(
+        &hospital,
+        &proposal_id,
+        &AttachmentKind::Diagnosis,
+        &document_hash(&env, "diagnosis"),
+        &Some(String::from_str(&env, "ipfs://diagnosis")),
+    );
+    assert_eq!(version, 1);
+    assert_eq!(env.events().all().len(), 1);
+    client.vote(&members.get(0).unwrap(), &proposal_id, &true);
+    
+    client.add_attachment(&hospital, &proposal_id, &AttachmentKind::CostEstimate, &document_hash(&env, "estimate"), &None);
+    client.vote(&members.get(1).unwrap(), &proposal_id, &true);
+    
+    assert_eq!(client.get_vote_attachment_version(&proposal_id, &members.get(0).unwrap()), 1);
+    assert_eq!(client.get_vote_attachment_version(&proposal_id, &members.get(1).unwrap()), 2);
+    
+    let seen = client.get_attachments(&proposal_id, &1);
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen.get(0).unwrap().kind, AttachmentKind::Diagnosis);
+    let latest = client.get_attachments(&proposal_id, &0);
+    assert_eq!(latest.len(), 2);
+    assert_eq!(latest.get(1).unwrap().hash, document_hash(&env, "estimate"));
+}
+
+#[test]
+#[should_panic(expected = "Proposal is not active")]
+fn test_attachment_after_finalize() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let proposal_id = voted_proposal(&client, &members, submit_in_category(&env, &client, &token, symbol_short!("trauma"), 1000), true);
+    
+    client.add_attachment(&hospital, &proposal_id, &AttachmentKind::Invoice, &document_hash(&env, "invoice"), &None); // Should panic
+}
+
+fn register_doctor(env: &Env, client: &EmergencyFundDAOClient, caller: &Address, hospital: &Address) -> Address {
+    let doctor = Address::generate(env);
+    client.register_doctor(caller, &doctor, hospital, &BytesN::from_array(env, &[9; 32]));
+    doctor
+}
+
+#[test]
+fn test_attestations_open_voting() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env);
+    client.set_attestation_rule(&admin, &2, &true);
+    
+    let own_doctor = register_doctor(&env, &client, &hospital, &hospital);
+    let other_hospital = Address::generate(&env);
+    let second_opinion = register_doctor(&env, &client, &admin, &other_hospital);
+    let third_opinion = register_doctor(&env, &client, &admin, &other_hospital);
+    
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    let statement = document_hash(&env, "necessity statement");
+    client.attest_medical_necessity(&own_doctor, &proposal_id, &statement);
+    client.attest_medical_necessity(&second_opinion, &proposal_id, &statement);
+    
+    // The hospital's own doctor does not count toward independent attestations
+    let result = client.try_vote(&members.get(0).unwrap(), &proposal_id, &true);
+    assert!(result.is_err());
+    
+    client.attest_medical_necessity(&third_opinion, &proposal_id, &statement);
+    client.vote(&members.get(0).unwrap(), &proposal_id, &true);
+    
+    assert_eq!(client.get_proposal(&proposal_id).attestations, 3);
+    let attestations = client.get_attestations(&proposal_id);
+    assert!(!attestations.get(0).unwrap().independent);
+    assert!(attestations.get(1).unwrap().independent);
+}
+
+#[test]
+#[should_panic(expected = "Medical necessity not attested")]
+fn test_vote_requires_attestation() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env);
+    client.set_attestation_rule(&admin, &1, &false);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    
+    client.vote(&members.get(0).unwrap(), &proposal_id, &true); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Doctor not registered")]
+fn test_attestation_by_unregistered_doctor() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, .. } = TestDao::new(&env);
+    let doctor = register_doctor(&env, &client, &hospital, &hospital);
+    client.remove_doctor(&hospital, &doctor);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    
+    client.attest_medical_necessity(&doctor, &proposal_id, &document_hash(&env, "statement")); // Should panic
+}
+
+fn review_pipeline(env: &Env, client: &EmergencyFundDAOClient, admin: &Address) -> (Address, Vec<Address>) {
+    client.set_review_pipeline(admin, &100, &100, &100, &2);
+    let coordinator = Address::generate(env);
+    client.set_review_role(admin, &coordinator, &ReviewRole::Coordinator, &true);
+    let reviewers = vec![env, Address::generate(env), Address::generate(env)];
+    for reviewer in reviewers.iter() {
+        client.set_review_role(admin, &reviewer, &ReviewRole::Reviewer, &true);
+    }
+    (coordinator, reviewers)
+}
+
+#[test]
+fn test_review_pipeline_opens_voting() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env);
+    let (coordinator, reviewers) = review_pipeline(&env, &client, &admin);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    assert_eq!(client.get_review(&proposal_id).stage, ReviewStage::Triage);
+    assert!(client.try_vote(&members.get(0).unwrap(), &proposal_id, &true).is_err());
+    
+    client.triage_proposal(&coordinator, &proposal_id, &true);
+    client.review_proposal(&reviewers.get(0).unwrap(), &proposal_id, &true);
+    assert_eq!(client.get_review(&proposal_id).stage, ReviewStage::MedicalReview);
+    
+    env.ledger().set_timestamp(50);
+    client.review_proposal(&reviewers.get(1).unwrap(), &proposal_id, &true);
+    let review = client.get_review(&proposal_id);
+    assert_eq!(review.stage, ReviewStage::Voting);
+    assert_eq!(review.deadline, 150);
+    
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    match client.get_proposal(&proposal_id).status {
+        ProposalStatus::Approved => {},
+        _ => panic!("Proposal should be approved"),
+    }
+}
+
+#[test]
+fn test_returned_proposal_revised_by_hospital() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, .. } = TestDao::new(&env);
+    let (coordinator, reviewers) = review_pipeline(&env, &client, &admin);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    
+    client.triage_proposal(&coordinator, &proposal_id, &true);
+    client.review_proposal(&reviewers.get(0).unwrap(), &proposal_id, &false);
+    assert_eq!(client.get_review(&proposal_id).stage, ReviewStage::Returned);
+    
+    client.revise_proposal(
+        &hospital,
+        &proposal_id,
+        &case_file(&env, "ID-1001", "Treatment with cost breakdown"),
+        &single_payee(&env, &hospital, 1200),
+    );
+    let review = client.get_review(&proposal_id);
+    assert_eq!(review.stage, ReviewStage::Triage);
+    assert_eq!(review.round, 2);
+    assert_eq!(client.get_proposal(&proposal_id).amount_requested, 1200);
+}
+
+#[test]
+#[should_panic(expected = "Stage deadline has passed")]
+fn test_triage_after_deadline() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, .. } = TestDao::new(&env);
+    let (coordinator, _reviewers) = review_pipeline(&env, &client, &admin);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    
+    env.ledger().set_timestamp(101);
+    client.triage_proposal(&coordinator, &proposal_id, &true); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Proposal is not active")]
+fn test_revise_withdrawn_proposal() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, .. } = TestDao::new(&env);
+    let (coordinator, _reviewers) = review_pipeline(&env, &client, &admin);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    client.triage_proposal(&coordinator, &proposal_id, &false);
+    client.withdraw_proposal(&hospital, &proposal_id);
+    
+    client.revise_proposal(
+        &hospital,
+        &proposal_id,
+        &case_file(&env, "ID-1001", "Treatment with cost breakdown"),
+        &single_payee(&env, &hospital, 1200),
+    ); // Should panic
+}
+
+#[test]
+fn test_committee_drawn_on_submission() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env).with_committees(1);
+    let conflicted = members.get(0).unwrap();
+    client.declare_conflict(&conflicted, &hospital);
+    
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    let committee = client.get_proposal(&proposal_id).committee;
+    assert_eq!(committee.len(), 3);
+    assert!(!committee.contains(&conflicted));
+    
+    let outsider = members.iter().find(|member| !committee.contains(member)).unwrap();
+    assert!(client.try_vote(&outsider, &proposal_id, &true).is_err());
+    
+    for member in committee.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    assert_eq!(client.get_proposal(&proposal_id).amount_approved, 1000);
+}
+
+#[test]
+fn test_committee_selection_is_deterministic_per_seed() {
+    let committee_indexes = |seed: u8| {
+        let env = Env::default();
+        env.mock_all_auths();
+        let TestDao { client, admin, hospital, token, mut members, .. } = TestDao::new(&env).with_committees(seed);
+        
+        // Neither the hospital nor a payee may sit on the committee
+        client.add_member(&admin, &hospital);
+        members.push_back(hospital.clone());
+        let payee = members.get(0).unwrap();
+        client.register_provider(&admin, &payee);
+        let line_items = vec![
+            &env,
+            LineItem { payee: hospital.clone(), amount: 800, role: PayeeRole::Hospital },
+            LineItem { payee: payee.clone(), amount: 200, role: PayeeRole::Pharmacy },
+        ];
+        let proposal_id = client.submit_proposal(
+            &hospital,
+            &case_file(&env, "ID-1001", "Treatment"),
+            &symbol_short!("trauma"),
+            &token,
+            &line_items,
+            &Map::new(&env),
+        );
+        
+        let committee = client.get_proposal(&proposal_id).committee;
+        assert!(!committee.contains(&hospital));
+        assert!(!committee.contains(&payee));
+        let mut indexes = [0_u32; 3];
+        for (i, member) in committee.iter().enumerate() {
+            indexes[i] = members.first_index_of(member).unwrap();
+        }
+        indexes
+    };
+    
+    assert_eq!(committee_indexes(7), committee_indexes(7));
+    assert_ne!(committee_indexes(7), committee_indexes(8));
+}
+
+#[test]
+fn test_absent_committee_members_replaced() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, .. } = TestDao::new(&env).with_committees(2);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    let committee = client.get_proposal(&proposal_id).committee;
+    let voter = committee.get(0).unwrap();
+    client.vote(&voter, &proposal_id, &true);
+    
+    env.ledger().set_timestamp(101);
+    assert_eq!(client.replace_absent_members(&proposal_id), 2);
+    
+    let proposal = client.get_proposal(&proposal_id);
+    assert_eq!(proposal.committee.len(), 3);
+    assert!(proposal.committee.contains(&voter));
+    assert!(!proposal.committee.contains(committee.get(1).unwrap()));
+    assert!(!proposal.committee.contains(committee.get(2).unwrap()));
+    assert_eq!(proposal.committee_deadline, 201);
+}
+
+#[test]
+fn test_committee_vote_period_starts_after_review() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, .. } = TestDao::new(&env).with_committees(4);
+    let (coordinator, reviewers) = review_pipeline(&env, &client, &admin);
+    client.set_committee_mode(&admin, &3, &50);
+    let proposal_id = client.submit_proposal(
+        &hospital,
+        &case_file(&env, "ID-1001", "Treatment"),
+        &symbol_short!("trauma"),
+        &token,
+        &single_payee(&env, &hospital, 1000),
+        &Map::new(&env),
+    );
+    assert_eq!(client.get_proposal(&proposal_id).committee_deadline, 0);
+    
+    env.ledger().set_timestamp(90);
+    client.triage_proposal(&coordinator, &proposal_id, &true);
+    
+    // The committee cannot be absent before it could vote
+    env.ledger().set_timestamp(150);
+    assert!(client.try_replace_absent_members(&proposal_id).is_err());
+    
+    env.ledger().set_timestamp(180);
+    for reviewer in reviewers.iter() {
+        client.review_proposal(&reviewer, &proposal_id, &true);
+    }
+    assert_eq!(client.get_proposal(&proposal_id).committee_deadline, 230);
+    
+    env.ledger().set_timestamp(220);
+    assert!(client.try_replace_absent_members(&proposal_id).is_err());
+    env.ledger().set_timestamp(231);
+    assert_eq!(client.replace_absent_members(&proposal_id), 3);
+}
+
+#[test]
+#[should_panic(expected = "Not enough eligible members for a committee")]
+fn test_committee_needs_enough_unconflicted_members() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env).with_committees(3);
+    for member in members.iter().take(4) {
+        client.declare_conflict(&member, &hospital);
+    }
+    
+    submit_for_patient(&env, &client, &token, "ID-1001", 1000); // Should panic
+}
+
+#[test]
+fn test_amend_before_votes() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    
+    client.amend_proposal(
+        &hospital,
+        &proposal_id,
+        &case_file(&env, "ID-1001", "Corrected estimate"),
+        &single_payee(&env, &hospital, 1400),
+        &false,
+    );
+    assert_eq!(client.get_proposal(&proposal_id).amount_requested, 1400);
+    
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &true);
+    }
+    client.finalize_proposal(&proposal_id);
+    assert_eq!(client.get_proposal(&proposal_id).amount_approved, 1400);
+}
+
+#[test]
+fn test_amend_drops_attestations() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, members, .. } = TestDao::new(&env);
+    client.set_attestation_rule(&admin, &1, &false);
+    let doctor = register_doctor(&env, &client, &admin, &hospital);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    client.attest_medical_necessity(&doctor, &proposal_id, &document_hash(&env, "statement"));
+    
+    client.amend_proposal(
+        &hospital,
+        &proposal_id,
+        &case_file(&env, "ID-1001", "Different treatment"),
+        &single_payee(&env, &hospital, 1400),
+        &false,
+    );
+    assert_eq!(client.get_proposal(&proposal_id).attestations, 0);
+    assert_eq!(client.get_attestations(&proposal_id).len(), 0);
+    assert!(client.try_vote(&members.get(0).unwrap(), &proposal_id, &true).is_err());
+    
+    // The doctor attests the amended case
+    client.attest_medical_necessity(&doctor, &proposal_id, &document_hash(&env, "amended statement"));
+    client.vote(&members.get(0).unwrap(), &proposal_id, &true);
+}
+
+#[test]
+fn test_amend_resets_votes() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    let voter = members.get(0).unwrap();
+    client.vote(&voter, &proposal_id, &true);
+    client.vote(&members.get(1).unwrap(), &proposal_id, &false);
+    
+    client.amend_proposal(
+        &hospital,
+        &proposal_id,
+        &case_file(&env, "ID-1001", "Corrected estimate"),
+        &single_payee(&env, &hospital, 1400),
+        &true,
+    );
+    
+    let events = env.events().all();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events.get(0).unwrap().0, client.address);
+    
+    let proposal = client.get_proposal(&proposal_id);
+    assert_eq!(proposal.votes_for, 0);
+    assert_eq!(proposal.votes_against, 0);
+    assert!(!client.has_voted(&proposal_id, &voter));
+    
+    // Members vote again on the amended proposal
+    client.vote(&voter, &proposal_id, &true);
+    assert_eq!(client.get_proposal(&proposal_id).votes_for, 1);
+}
+
+#[test]
+#[should_panic(expected = "Proposal already has votes")]
+fn test_amend_with_votes_requires_reset() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    client.vote(&members.get(0).unwrap(), &proposal_id, &true);
+    
+    client.amend_proposal(
+        &hospital,
+        &proposal_id,
+        &case_file(&env, "ID-1001", "Corrected estimate"),
+        &single_payee(&env, &hospital, 1400),
+        &false,
+    ); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Payee sits on the proposal's committee")]
+fn test_amend_cannot_pay_committee_member() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, hospital, token, .. } = TestDao::new(&env).with_committees(1);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    let member = client.get_proposal(&proposal_id).committee.get(0).unwrap();
+    client.register_provider(&admin, &member);
+    
+    client.amend_proposal(
+        &hospital,
+        &proposal_id,
+        &case_file(&env, "ID-1001", "Corrected estimate"),
+        &vec![&env, LineItem { payee: member, amount: 1000, role: PayeeRole::Pharmacy }],
+        &false,
+    ); // Should panic
+}
+
+#[test]
+fn test_withdraw_releases_reservations() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, .. } = TestDao::new(&env);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 4000);
+    let donor = Address::generate(&env);
+    StellarAssetClient::new(&env, &token).mint(&donor, &1500);
+    client.add_funds(&donor, &token, &1500, &Some(Restriction::Proposal(proposal_id)));
+    let alice = contribute(&env, &client, &token, proposal_id, 2000);
+    
+    client.withdraw_proposal(&hospital, &proposal_id);
+    
+    match client.get_proposal(&proposal_id).status {
+        ProposalStatus::Cancelled => {},
+        _ => panic!("Proposal should be cancelled"),
+    }
+    // The earmark goes back to its donor, not into general funds
+    assert_eq!(client.get_restricted_balance(&token, &Restriction::Proposal(proposal_id)), 0);
+    assert_eq!(client.get_treasury_balance(&token), 0);
+    assert_eq!(client.claim(&donor, &token), 1500);
+    assert_eq!(client.refund_contribution(&alice, &proposal_id), 2000);
+}
+
+#[test]
+#[should_panic(expected = "Only the submitting hospital can withdraw a proposal")]
+fn test_withdraw_by_other_hospital() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, .. } = TestDao::new(&env);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    
+    client.withdraw_proposal(&Address::generate(&env), &proposal_id); // Should panic
+}
+
+fn appeal(env: &Env, client: &EmergencyFundDAOClient, proposal_id: u64, evidence: &str) -> u64 {
+    let hospital = client.get_authorized_hospital();
+    client.appeal_proposal(
+        &hospital,
+        &proposal_id,
+        &case_file(env, "ID-1001", "Second opinion"),
+        &single_payee(env, &hospital, 1000),
+        &vec![env, document_hash(env, evidence)],
+        &Map::new(env),
+    )
+}
+
+#[test]
+fn test_appeal_needs_higher_threshold() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, members, .. } = TestDao::new(&env);
+    let original = voted_proposal(&client, &members, submit_for_patient(&env, &client, &token, "ID-1001", 1000), false);
+    
+    let first_appeal = appeal(&env, &client, original, "second-opinion");
+    assert_eq!(client.get_proposal(&original).appealed_by, Some(first_appeal));
+    assert_eq!(client.get_proposal(&first_appeal).appeal_of, Some(original));
+    let evidence = client.get_attachments(&first_appeal, &0);
+    assert_eq!(evidence.len(), 1);
+    assert_eq!(evidence.get(0).unwrap().kind, AttachmentKind::AppealEvidence);
+    
+    // Two thirds would pass a regular proposal but not an appeal
+    client.vote(&members.get(0).unwrap(), &first_appeal, &true);
+    client.vote(&members.get(1).unwrap(), &first_appeal, &true);
+    client.vote(&members.get(2).unwrap(), &first_appeal, &false);
+    client.finalize_proposal(&first_appeal);
+    match client.get_proposal(&first_appeal).status {
+        ProposalStatus::Rejected => {},
+        _ => panic!("Appeal should be rejected"),
+    }
+    
+    let second_appeal = appeal(&env, &client, first_appeal, "scan-results");
+    for member in members.iter() {
+        client.vote(&member, &second_appeal, &true);
+    }
+    client.finalize_proposal(&second_appeal);
+    assert_eq!(client.get_proposal(&second_appeal).amount_approved, 1000);
+    
+    let chain = vec![&env, original, first_appeal, second_appeal];
+    assert_eq!(client.get_appeal_chain(&first_appeal), chain);
+}
+
+#[test]
+fn test_appeal_drawn_to_different_committee() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, .. } = TestDao::new(&env).with_committees(3);
+    let original = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    let committee = client.get_proposal(&original).committee;
+    for member in committee.iter() {
+        client.vote(&member, &original, &false);
+    }
+    client.finalize_proposal(&original);
+    
+    let appeal_id = appeal(&env, &client, original, "second-opinion");
+    let appeal_committee = client.get_proposal(&appeal_id).committee;
+    assert_eq!(appeal_committee.len(), 3);
+    for member in appeal_committee.iter() {
+        assert!(!committee.contains(&member));
+    }
+}
+
+#[test]
+fn test_appeal_replacements_exclude_original_committee() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, token, .. } = TestDao::new(&env).with_committees(3).with_members(9);
+    let original = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    let committee = client.get_proposal(&original).committee;
+    for member in committee.iter() {
+        client.vote(&member, &original, &false);
+    }
+    client.finalize_proposal(&original);
+    let appeal_id = appeal(&env, &client, original, "second-opinion");
+    
+    // Nobody on the appeal committee votes; only the 3 members untouched by
+    // the original decision or the appeal can replace them
+    env.ledger().set_timestamp(101);
+    assert_eq!(client.replace_absent_members(&appeal_id), 3);
+    for member in client.get_proposal(&appeal_id).committee.iter() {
+        assert!(!committee.contains(&member));
+    }
+}
+
+#[test]
+#[should_panic(expected = "Appeal window has closed")]
+fn test_appeal_after_window() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, admin, token, members, .. } = TestDao::new(&env);
+    client.set_appeal_rule(&admin, &100, &80);
+    let original = voted_proposal(&client, &members, submit_for_patient(&env, &client, &token, "ID-1001", 1000), false);
+    
+    env.ledger().set_timestamp(101);
+    appeal(&env, &client, original, "second-opinion"); // Should panic
+}
+
+#[test]
+#[should_panic(expected = "Evidence was already submitted")]
+fn test_appeal_with_old_evidence() {
+    let env = Env::default();
+    env.mock_all_auths();
+    
+    let TestDao { client, hospital, token, members, .. } = TestDao::new(&env);
+    let proposal_id = submit_for_patient(&env, &client, &token, "ID-1001", 1000);
+    client.add_attachment(&hospital, &proposal_id, &AttachmentKind::Diagnosis, &document_hash(&env, "diagnosis"), &None);
+    for member in members.iter() {
+        client.vote(&member, &proposal_id, &false);
+    }
+    client.finalize_proposal(&proposal_id);
+    
+    appeal(&env, &client, proposal_id, "diagnosis"); // Should panic
+}